@@ -1,12 +1,227 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
 use parse_display::{Display, FromStr};
+use std::cmp::Reverse;
 
-const ROCK: usize = 1;
-const PAPER: usize = 2;
-const SCISSORS: usize = 3;
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Loss,
+    Draw,
+    Win,
+}
+
+impl Outcome {
+    pub const ALL: [Self; 3] = [Self::Loss, Self::Draw, Self::Win];
+}
+
+// A game over `N` shapes where each shape beats a fixed set of the others
+#[derive(Clone, Debug)]
+pub struct Game {
+    shape_scores: Vec<usize>,
+    outcome_scores: [usize; 3],
+    beats: Vec<Vec<bool>>,
+}
+
+impl Game {
+    // `beats[i]` lists the shapes that shape `i` wins against. Fails if a shape is unknown, beats
+    // itself, or two shapes beat each other.
+    #[must_use]
+    pub fn new(
+        shape_scores: Vec<usize>,
+        outcome_scores: [usize; 3],
+        beats: &[Vec<usize>],
+    ) -> Option<Self> {
+        let num_shapes = shape_scores.len();
+        if beats.len() > num_shapes {
+            return None;
+        }
+
+        let mut table = vec![vec![false; num_shapes]; num_shapes];
+        for (winner, losers) in beats.iter().enumerate() {
+            for &loser in losers {
+                *table[winner].get_mut(loser)? = true;
+            }
+        }
+
+        let consistent = (0..num_shapes).all(|shape| {
+            !table[shape][shape]
+                && (0..shape).all(|other| !(table[shape][other] && table[other][shape]))
+        });
+
+        consistent.then_some(Self {
+            shape_scores,
+            outcome_scores,
+            beats: table,
+        })
+    }
+
+    // Shape `i` beats the shapes `i - 1`, `i - 3`, ... (wrapping around), shapes score `i + 1`.
+    // With 3 shapes this is rock-paper-scissors, with 5 rock-paper-scissors-Spock-lizard. An even
+    // number of shapes can't be balanced like that, the opposite shape would beat and lose to `i`.
+    #[must_use]
+    pub fn cyclic(num_shapes: usize) -> Option<Self> {
+        if num_shapes.is_multiple_of(2) {
+            return None;
+        }
+
+        let beats = (0..num_shapes)
+            .map(|shape| {
+                (1..num_shapes)
+                    .step_by(2)
+                    .map(|offset| (shape + num_shapes - offset) % num_shapes)
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+
+        Self::new((1..=num_shapes).collect(), [0, 3, 6], &beats)
+    }
+
+    #[must_use]
+    pub fn rock_paper_scissors() -> Self {
+        Self {
+            shape_scores: vec![1, 2, 3],
+            outcome_scores: [0, 3, 6],
+            beats: vec![
+                vec![false, false, true],
+                vec![true, false, false],
+                vec![false, true, false],
+            ],
+        }
+    }
+
+    #[must_use]
+    pub const fn num_shapes(&self) -> usize {
+        self.shape_scores.len()
+    }
+
+    #[must_use]
+    pub fn outcome(&self, mine: usize, theirs: usize) -> Outcome {
+        if self.beats[mine][theirs] {
+            Outcome::Win
+        } else if self.beats[theirs][mine] {
+            Outcome::Loss
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    #[must_use]
+    pub fn score(&self, mine: usize, theirs: usize) -> usize {
+        self.shape_scores[mine] + self.outcome_scores[self.outcome(mine, theirs) as usize]
+    }
+
+    // The shape to play against `theirs` to get the wanted outcome. If several shapes do, the
+    // highest scoring one is picked, ties going to the lowest index.
+    #[must_use]
+    pub fn shape_for(&self, theirs: usize, outcome: Outcome) -> Option<usize> {
+        (0..self.num_shapes())
+            .filter(|&mine| self.outcome(mine, theirs) == outcome)
+            .max_by_key(|&mine| (self.shape_scores[mine], Reverse(mine)))
+    }
+
+    // Second column of the guide is the shape to play
+    #[must_use]
+    pub fn score_as_shapes(&self, guide: &[(usize, usize)]) -> Option<usize> {
+        guide
+            .iter()
+            .map(|&(theirs, mine)| {
+                (theirs < self.num_shapes() && mine < self.num_shapes())
+                    .then(|| self.score(mine, theirs))
+            })
+            .sum()
+    }
+
+    // Second column of the guide is the outcome to reach
+    #[must_use]
+    pub fn score_as_outcomes(&self, guide: &[(usize, usize)]) -> Option<usize> {
+        guide
+            .iter()
+            .map(|&(theirs, outcome)| {
+                if theirs >= self.num_shapes() {
+                    return None;
+                }
+
+                let mine = self.shape_for(theirs, *Outcome::ALL.get(outcome)?)?;
+                Some(self.score(mine, theirs))
+            })
+            .sum()
+    }
+}
+
+// How the second column of a guide is read: `Shapes(v)` plays `v[i]` for symbol `i`,
+// `Outcomes(v)` aims for `v[i]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decoding {
+    Shapes(Vec<usize>),
+    Outcomes(Vec<Outcome>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedScore {
+    pub decoding: Decoding,
+    pub score: usize,
+}
 
-const WIN: usize = 6;
-const DRAW: usize = 3;
+#[derive(Clone, Debug, Default)]
+pub struct DecoderReport {
+    pub scores: Vec<DecodedScore>,
+}
+
+impl DecoderReport {
+    #[must_use]
+    pub fn best(&self) -> Option<&DecodedScore> {
+        self.scores.iter().max_by_key(|it| it.score)
+    }
+
+    #[must_use]
+    pub fn worst(&self) -> Option<&DecodedScore> {
+        self.scores.iter().min_by_key(|it| it.score)
+    }
+}
+
+impl Game {
+    // Scores the guide for every way of mapping its second column to shapes or outcomes. Symbols
+    // may share a meaning, so there are `shapes ^ symbols + 3 ^ symbols` decodings.
+    #[must_use]
+    pub fn search_decodings(&self, guide: &[(usize, usize)]) -> DecoderReport {
+        let num_symbols = guide.iter().map(|&(_, rhs)| rhs + 1).max().unwrap_or(0);
+
+        let remap = |mapping: &[usize]| {
+            guide
+                .iter()
+                .map(|&(lhs, rhs)| (lhs, mapping[rhs]))
+                .collect_vec()
+        };
+
+        let as_shapes = (0..num_symbols)
+            .map(|_| 0..self.num_shapes())
+            .multi_cartesian_product()
+            .filter_map(|mapping| {
+                let score = self.score_as_shapes(&remap(&mapping))?;
+                Some(DecodedScore {
+                    decoding: Decoding::Shapes(mapping),
+                    score,
+                })
+            });
+
+        let as_outcomes = (0..num_symbols)
+            .map(|_| 0..Outcome::ALL.len())
+            .multi_cartesian_product()
+            .filter_map(|mapping| {
+                let score = self.score_as_outcomes(&remap(&mapping))?;
+                Some(DecodedScore {
+                    decoding: Decoding::Outcomes(
+                        mapping.iter().map(|&it| Outcome::ALL[it]).collect(),
+                    ),
+                    score,
+                })
+            });
+
+        DecoderReport {
+            scores: as_shapes.chain(as_outcomes).collect(),
+        }
+    }
+}
 
 #[derive(Display, FromStr, Copy, Clone)]
 #[display("{lhs} {rhs}")]
@@ -15,54 +230,30 @@ struct ParsedInput {
     rhs: char,
 }
 
+// Columns are turned into indices, 'A'/'X' being 0, 'B'/'Y' being 1, ...
+#[must_use]
 #[aoc_generator(day2)]
-pub fn generate(inp: &str) -> Vec<(char, char)> {
+pub fn generate(inp: &str) -> Vec<(usize, usize)> {
     inp.lines()
         .filter_map(|it| it.parse::<ParsedInput>().ok())
-        .map(|ParsedInput { lhs, rhs }| (lhs, rhs))
+        .filter_map(|ParsedInput { lhs, rhs }| {
+            let lhs = (lhs as usize).checked_sub('A' as usize)?;
+            let rhs = (rhs as usize).checked_sub('X' as usize)?;
+            Some((lhs, rhs))
+        })
         .collect()
 }
 
+#[must_use]
 #[aoc(day2, part1)]
-pub fn part1(inp: &[(char, char)]) -> usize {
-    inp.iter()
-        .map(|(lhs, rhs)| match (lhs, rhs) {
-            ('A', 'X') => ROCK + DRAW,
-            ('A', 'Y') => PAPER + WIN,
-            ('A', 'Z') => SCISSORS,
-
-            ('B', 'X') => ROCK,
-            ('B', 'Y') => PAPER + DRAW,
-            ('B', 'Z') => SCISSORS + WIN,
-
-            ('C', 'X') => ROCK + WIN,
-            ('C', 'Y') => PAPER,
-            ('C', 'Z') => SCISSORS + DRAW,
-
-            _ => unreachable!("Unknown pattern"),
-        })
-        .sum()
+pub fn part1(inp: &[(usize, usize)]) -> Option<usize> {
+    Game::rock_paper_scissors().score_as_shapes(inp)
 }
 
+#[must_use]
 #[aoc(day2, part2)]
-pub fn part2(inp: &[(char, char)]) -> usize {
-    inp.iter()
-        .map(|(lhs, rhs)| match (lhs, rhs) {
-            ('A', 'X') => SCISSORS,
-            ('A', 'Y') => ROCK + DRAW,
-            ('A', 'Z') => PAPER + WIN,
-
-            ('B', 'X') => ROCK,
-            ('B', 'Y') => PAPER + DRAW,
-            ('B', 'Z') => SCISSORS + WIN,
-
-            ('C', 'X') => PAPER,
-            ('C', 'Y') => SCISSORS + DRAW,
-            ('C', 'Z') => ROCK + WIN,
-
-            _ => unreachable!("Unknown pattern"),
-        })
-        .sum()
+pub fn part2(inp: &[(usize, usize)]) -> Option<usize> {
+    Game::rock_paper_scissors().score_as_outcomes(inp)
 }
 
 #[cfg(test)]
@@ -77,13 +268,114 @@ mod tests {
     fn test_sample_p1() {
         let data = generate(TEST_INPUT);
         let res = part1(&data);
-        assert_eq!(res, 15);
+        assert_eq!(res, Some(15));
     }
 
     #[test]
     fn test_sample_p2() {
         let data = generate(TEST_INPUT);
         let res = part2(&data);
-        assert_eq!(res, 12);
+        assert_eq!(res, Some(12));
+    }
+
+    #[test]
+    fn test_rock_paper_scissors_lizard_spock() {
+        // Rock, Paper, Scissors, Spock, Lizard
+        let game = Game::cyclic(5).expect("Odd number of shapes");
+
+        assert_eq!(game.outcome(3, 2), Outcome::Win);
+        assert_eq!(game.outcome(3, 0), Outcome::Win);
+        assert_eq!(game.outcome(4, 3), Outcome::Win);
+        assert_eq!(game.outcome(4, 1), Outcome::Win);
+        assert_eq!(game.outcome(0, 4), Outcome::Win);
+        assert_eq!(game.outcome(0, 3), Outcome::Loss);
+        assert_eq!(game.outcome(1, 1), Outcome::Draw);
+
+        let guide = vec![(0, 3), (4, 0), (2, 2)];
+        assert_eq!(game.score_as_shapes(&guide), Some(4 + 6 + 1 + 6 + 3 + 3));
+
+        // Lose against rock with lizard, beat lizard with scissors, draw scissors
+        let guide = vec![(0, 0), (4, 2), (2, 1)];
+        assert_eq!(game.score_as_outcomes(&guide), Some(5 + 9 + 6));
+    }
+
+    #[test]
+    fn test_shape_for_picks_highest_score() {
+        let game = Game::cyclic(5).expect("Odd number of shapes");
+
+        // Both paper and Spock beat rock, both scissors and lizard lose to it
+        assert_eq!(game.shape_for(0, Outcome::Win), Some(3));
+        assert_eq!(game.shape_for(0, Outcome::Loss), Some(4));
+        assert_eq!(game.shape_for(0, Outcome::Draw), Some(0));
+
+        // Shapes 1 and 2 both beat shape 0 and score the same
+        let game =
+            Game::new(vec![1, 5, 5], [0, 3, 6], &[vec![], vec![0], vec![0]]).expect("Valid game");
+        assert_eq!(game.shape_for(0, Outcome::Win), Some(1));
+        assert_eq!(game.shape_for(1, Outcome::Draw), Some(1));
+    }
+
+    #[test]
+    fn test_invalid_games() {
+        assert!(Game::cyclic(4).is_none());
+        assert!(Game::cyclic(0).is_none());
+        assert_eq!(
+            Game::cyclic(3).map(|it| it.beats),
+            Some(Game::rock_paper_scissors().beats)
+        );
+
+        // Both beat each other
+        assert!(Game::new(vec![1, 2], [0, 3, 6], &[vec![1], vec![0]]).is_none());
+        // Beats itself
+        assert!(Game::new(vec![1, 2], [0, 3, 6], &[vec![0]]).is_none());
+        // Unknown shape
+        assert!(Game::new(vec![1, 2], [0, 3, 6], &[vec![2]]).is_none());
+
+        let game = Game::new(vec![1, 2], [0, 3, 6], &[vec![1]]).expect("Valid game");
+        assert_eq!(game.outcome(0, 1), Outcome::Win);
+        assert_eq!(game.outcome(1, 0), Outcome::Loss);
+    }
+
+    #[test]
+    fn test_invalid_guide() {
+        let game = Game::rock_paper_scissors();
+        assert_eq!(game.score_as_shapes(&[(0, 3)]), None);
+        assert_eq!(game.score_as_outcomes(&[(3, 0)]), None);
+    }
+
+    #[test]
+    fn test_search_decodings() {
+        let data = generate(TEST_INPUT);
+        let report = Game::rock_paper_scissors().search_decodings(&data);
+
+        // 27 ways to read the column as shapes, 27 as outcomes
+        assert_eq!(report.scores.len(), 54);
+
+        assert!(report.scores.contains(&DecodedScore {
+            decoding: Decoding::Shapes(vec![0, 1, 2]),
+            score: 15,
+        }));
+        assert!(report.scores.contains(&DecodedScore {
+            decoding: Decoding::Outcomes(vec![Outcome::Loss, Outcome::Draw, Outcome::Win]),
+            score: 12,
+        }));
+
+        // Symbols can share a meaning
+        assert!(report.scores.contains(&DecodedScore {
+            decoding: Decoding::Shapes(vec![1, 1, 1]),
+            score: 8 + 5 + 2,
+        }));
+
+        assert_eq!(report.best().map(|it| it.score), Some(24));
+        assert_eq!(report.worst().map(|it| it.score), Some(6));
+
+        // More symbols than shapes or outcomes
+        let guide = vec![(0, 0), (1, 1), (2, 2), (0, 3)];
+        let report = Game::rock_paper_scissors().search_decodings(&guide);
+        assert_eq!(report.scores.len(), 81 + 81);
+        assert_eq!(
+            report.best().map(|it| it.score),
+            Some(4 * 6 + 2 + 3 + 1 + 2)
+        );
     }
 }
@@ -1,55 +1,131 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::ops::Range;
 
+// Items are stored as a mask, bit `n` being the item with priority `n` (1..=52)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rucksack {
+    compartments: [u64; 2],
+}
+
+impl Rucksack {
+    const fn items(self) -> u64 {
+        self.compartments[0] | self.compartments[1]
+    }
+}
+
+const fn priority(item: u8) -> Option<u32> {
+    match item {
+        b'a'..=b'z' => Some((item - b'a') as u32 + 1),
+        b'A'..=b'Z' => Some((item - b'A') as u32 + 27),
+        _ => None,
+    }
+}
+
+const fn item_for_priority(priority: u32) -> char {
+    if priority <= 26 {
+        (b'a' + priority as u8 - 1) as char
+    } else {
+        (b'A' + priority as u8 - 27) as char
+    }
+}
+
+fn item_mask(items: &[u8]) -> u64 {
+    items
+        .iter()
+        .filter_map(|&it| priority(it))
+        .fold(0, |acc, it| acc | (1 << it))
+}
+
+#[must_use]
 #[aoc_generator(day3)]
-pub fn generate(inp: &str) -> Vec<String> {
-    inp.lines().map(ToString::to_string).collect()
+pub fn generate(inp: &str) -> Vec<Rucksack> {
+    inp.lines()
+        .map(|it| {
+            let (lhs, rhs) = it.as_bytes().split_at(it.len() / 2);
+            Rucksack {
+                compartments: [item_mask(lhs), item_mask(rhs)],
+            }
+        })
+        .collect()
 }
 
-fn sum_common_chars(common: &[char]) -> usize {
-    common.iter().fold(0, |acc, &it| {
-        acc + if it.is_lowercase() {
-            (1 + (it as u8) - b'a') as usize
-        } else {
-            (27 + (it as u8) - b'A') as usize
-        }
-    })
+// Items shared by the rucksacks in `rucksacks`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharedItems {
+    pub rucksacks: Range<usize>,
+    mask: u64,
 }
 
-#[aoc(day3, part1)]
-pub fn part1(inp: &[String]) -> usize {
-    inp.iter().fold(0, |acc, it| {
-        let (r1, r2) = it.split_at(it.len() / 2);
-        let s1 = r1.chars().collect::<HashSet<_>>();
-        let s2 = r2.chars().collect::<HashSet<_>>();
+impl SharedItems {
+    pub fn items(&self) -> Vec<char> {
+        (1..=52)
+            .filter(|it| self.mask & (1 << it) != 0)
+            .map(item_for_priority)
+            .collect()
+    }
 
-        let shared = s1.intersection(&s2).copied().collect_vec();
+    // The shared item, if there is exactly one
+    #[must_use]
+    pub fn item(&self) -> Option<char> {
+        self.mask
+            .is_power_of_two()
+            .then(|| item_for_priority(self.mask.trailing_zeros()))
+    }
 
-        acc + sum_common_chars(&shared)
-    })
+    #[must_use]
+    pub fn priority_sum(&self) -> u32 {
+        (1..=52).filter(|it| self.mask & (1 << it) != 0).sum()
+    }
+}
+
+#[must_use]
+pub fn shared_in_compartments(inp: &[Rucksack]) -> Vec<SharedItems> {
+    inp.iter()
+        .enumerate()
+        .map(|(idx, it)| SharedItems {
+            rucksacks: idx..idx + 1,
+            mask: it.compartments[0] & it.compartments[1],
+        })
+        .collect()
+}
+
+// Trailing rucksacks that don't form a complete group are ignored
+#[must_use]
+pub fn shared_in_groups(inp: &[Rucksack], group_size: usize) -> Vec<SharedItems> {
+    if group_size == 0 {
+        return vec![];
+    }
+
+    inp.chunks_exact(group_size)
+        .enumerate()
+        .map(|(idx, group)| SharedItems {
+            rucksacks: idx * group_size..(idx + 1) * group_size,
+            mask: group.iter().fold(u64::MAX, |acc, it| acc & it.items()),
+        })
+        .collect()
+}
+
+// Everything that doesn't share exactly one item
+#[must_use]
+pub fn rule_violations(shared: &[SharedItems]) -> Vec<&SharedItems> {
+    shared.iter().filter(|it| it.item().is_none()).collect_vec()
+}
+
+#[aoc(day3, part1)]
+pub fn part1(inp: &[Rucksack]) -> u32 {
+    shared_in_compartments(inp)
+        .iter()
+        .map(SharedItems::priority_sum)
+        .sum()
 }
 
 #[aoc(day3, part2)]
-pub fn part2(inp: &[String]) -> usize {
-    inp.chunks_exact(3).fold(0, |acc, it| match it {
-        [l1, l2, l3] => {
-            let s1 = l1.chars().collect::<HashSet<_>>();
-            let s2 = l2.chars().collect::<HashSet<_>>();
-            let s3 = l3.chars().collect::<HashSet<_>>();
-
-            let shared_in_group = s1
-                .intersection(&s2)
-                .copied()
-                .collect::<HashSet<_>>()
-                .intersection(&s3)
-                .copied()
-                .collect_vec();
-
-            acc + sum_common_chars(&shared_in_group)
-        }
-        _ => unreachable!(),
-    })
+pub fn part2(inp: &[Rucksack]) -> u32 {
+    shared_in_groups(inp, 3)
+        .iter()
+        .map(SharedItems::priority_sum)
+        .sum()
 }
 
 #[cfg(test)]
@@ -76,4 +152,37 @@ mod tests {
         let res = part2(&data);
         assert_eq!(res, 70);
     }
+
+    #[test]
+    fn test_shared_items() {
+        let data = generate(TEST_DATA);
+
+        let items = shared_in_compartments(&data)
+            .iter()
+            .filter_map(SharedItems::item)
+            .collect::<String>();
+        assert_eq!(items, "pLPvts");
+
+        let groups = shared_in_groups(&data, 3);
+        assert_eq!(groups[0].item(), Some('r'));
+        assert_eq!(groups[1].item(), Some('Z'));
+        assert_eq!(groups[1].rucksacks, 3..6);
+        assert!(rule_violations(&groups).is_empty());
+    }
+
+    #[test]
+    fn test_rule_violations() {
+        let data = generate("abab\nabca\nxy");
+
+        let shared = shared_in_compartments(&data);
+        let violations = rule_violations(&shared);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].items(), vec!['a', 'b']);
+        assert!(violations[1].items().is_empty());
+        assert_eq!(violations[1].rucksacks, 2..3);
+
+        let groups = shared_in_groups(&data, 2);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].items(), vec!['a', 'b']);
+    }
 }
@@ -1,7 +1,10 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use parse_display::{Display, FromStr};
+use itertools::Itertools;
+use parse_display::{Display, FromStr, ParseError};
+use std::str::FromStr;
 
-#[derive(Display, FromStr, Copy, Clone, Debug)]
+// Inclusive range of sections
+#[derive(Display, FromStr, Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[display("{lo}-{hi}")]
 pub struct Range {
     lo: i64,
@@ -9,38 +12,336 @@ pub struct Range {
 }
 
 impl Range {
-    const fn fully_contains(&self, other: &Self) -> bool {
+    #[must_use]
+    pub const fn new(lo: i64, hi: i64) -> Self {
+        Self { lo, hi }
+    }
+
+    #[must_use]
+    pub const fn lo(&self) -> i64 {
+        self.lo
+    }
+
+    #[must_use]
+    pub const fn hi(&self) -> i64 {
+        self.hi
+    }
+
+    // The full i64 line has one more section than fits a u64, so this saturates
+    #[must_use]
+    pub const fn len(&self) -> u64 {
+        if self.hi < self.lo {
+            0
+        } else {
+            self.hi.abs_diff(self.lo).saturating_add(1)
+        }
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.hi < self.lo
+    }
+
+    #[must_use]
+    pub const fn fully_contains(&self, other: &Self) -> bool {
         other.lo >= self.lo && other.hi <= self.hi
     }
 
-    const fn overlaps(&self, other: &Self) -> bool {
+    #[must_use]
+    pub const fn overlaps(&self, other: &Self) -> bool {
         self.lo <= other.hi && self.hi >= other.lo
     }
+
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let res = Self::new(self.lo.max(other.lo), self.hi.min(other.hi));
+        (!res.is_empty()).then_some(res)
+    }
+}
+
+// Sorted, non-overlapping and non-adjacent ranges
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    ranges: Vec<Range>,
+}
+
+impl IntervalSet {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_ranges<I: IntoIterator<Item = Range>>(ranges: I) -> Self {
+        let sorted = ranges
+            .into_iter()
+            .filter(|it| !it.is_empty())
+            .sorted_unstable();
+
+        let mut merged: Vec<Range> = vec![];
+        for range in sorted {
+            match merged.last_mut() {
+                Some(last) if range.lo <= last.hi.saturating_add(1) => {
+                    last.hi = last.hi.max(range.hi);
+                }
+                _ => merged.push(range),
+            }
+        }
+
+        Self { ranges: merged }
+    }
+
+    #[must_use]
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    // Number of sections covered
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.ranges
+            .iter()
+            .fold(0, |acc: u64, it| acc.saturating_add(it.len()))
+    }
+
+    #[must_use]
+    pub fn contains(&self, section: i64) -> bool {
+        let idx = self.ranges.partition_point(|it| it.hi < section);
+        self.ranges.get(idx).is_some_and(|it| it.lo <= section)
+    }
+
+    #[must_use]
+    pub fn covers(&self, range: &Range) -> bool {
+        let idx = self.ranges.partition_point(|it| it.hi < range.lo);
+        self.ranges
+            .get(idx)
+            .is_some_and(|it| it.fully_contains(range))
+    }
+
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        Self::from_ranges(self.ranges.iter().chain(&other.ranges).copied())
+    }
+
+    #[must_use]
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut result = vec![];
+        let (mut lhs, mut rhs) = (0, 0);
+
+        while lhs < self.ranges.len() && rhs < other.ranges.len() {
+            let (l, r) = (self.ranges[lhs], other.ranges[rhs]);
+            result.extend(l.intersection(&r));
+
+            if l.hi < r.hi {
+                lhs += 1;
+            } else {
+                rhs += 1;
+            }
+        }
+
+        Self { ranges: result }
+    }
+
+    #[must_use]
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut result = vec![];
+        let mut other_iter = other.ranges.iter().peekable();
+
+        for &range in &self.ranges {
+            let mut lo = range.lo;
+
+            while let Some(cut) = other_iter.peek() {
+                if cut.hi < lo {
+                    other_iter.next();
+                    continue;
+                }
+
+                if cut.lo > range.hi {
+                    break;
+                }
+
+                if cut.lo > lo {
+                    result.push(Range::new(lo, cut.lo - 1));
+                }
+
+                if cut.hi >= range.hi {
+                    lo = range.hi.saturating_add(1);
+                    break;
+                }
+
+                lo = cut.hi + 1;
+                other_iter.next();
+            }
+
+            if lo <= range.hi {
+                result.push(Range::new(lo, range.hi));
+            }
+        }
+
+        Self { ranges: result }
+    }
+}
+
+// Sweep over all range boundaries, returning the covered segments and how many ranges cover each
+#[must_use]
+pub fn coverage_profile(ranges: &[Range]) -> Vec<(Range, usize)> {
+    // Ends are exclusive here, so a range ending at i64::MAX never closes
+    let events = ranges
+        .iter()
+        .filter(|it| !it.is_empty())
+        .flat_map(|it| {
+            [Some((it.lo, 1)), it.hi.checked_add(1).map(|end| (end, -1))]
+                .into_iter()
+                .flatten()
+        })
+        .sorted_unstable()
+        .collect_vec();
+
+    let mut result = vec![];
+    let mut depth = 0i64;
+
+    for (idx, &(section, delta)) in events.iter().enumerate() {
+        depth += delta;
+
+        // Only emit a segment once all events at this section are applied
+        if events.get(idx + 1).is_some_and(|it| it.0 == section) || depth == 0 {
+            continue;
+        }
+
+        let end = events.get(idx + 1).map_or(i64::MAX, |it| it.0 - 1);
+        result.push((Range::new(section, end), depth as usize));
+    }
+
+    result
+}
+
+#[must_use]
+pub fn covered_by_at_least(ranges: &[Range], k: usize) -> IntervalSet {
+    if k == 0 {
+        return IntervalSet::from_ranges([Range::new(i64::MIN, i64::MAX)]);
+    }
+
+    IntervalSet::from_ranges(
+        coverage_profile(ranges)
+            .into_iter()
+            .filter(|(_, depth)| *depth >= k)
+            .map(|(range, _)| range),
+    )
+}
+
+// Indices of the ranges that are fully covered by the other ranges (each one on its own)
+#[must_use]
+pub fn redundant_assignments(ranges: &[Range]) -> Vec<usize> {
+    let overlapping = covered_by_at_least(ranges, 2);
+
+    ranges
+        .iter()
+        .positions(|it| overlapping.covers(it))
+        .collect()
+}
+
+// The lowest section covered by the most ranges, and how many ranges cover it
+#[must_use]
+pub fn most_overlap(ranges: &[Range]) -> Option<(i64, usize)> {
+    coverage_profile(ranges)
+        .into_iter()
+        .fold(None, |best, (range, depth)| match best {
+            Some((_, best_depth)) if best_depth >= depth => best,
+            _ => Some((range.lo, depth)),
+        })
+}
+
+// Indices of as few ranges as possible that still cover every section any range covers
+#[must_use]
+pub fn minimum_cover(ranges: &[Range]) -> Vec<usize> {
+    let by_start = (0..ranges.len())
+        .filter(|&idx| !ranges[idx].is_empty())
+        .sorted_by_key(|&idx| ranges[idx].lo)
+        .collect_vec();
+
+    let mut result = vec![];
+    let mut next = 0;
+
+    for component in IntervalSet::from_ranges(ranges.iter().copied()).ranges() {
+        let mut lo = Some(component.lo);
+
+        // Greedily take the range reaching the furthest among those starting before `lo`
+        while let Some(start) = lo.filter(|it| *it <= component.hi) {
+            let mut best: Option<usize> = None;
+            while next < by_start.len() && ranges[by_start[next]].lo <= start {
+                let idx = by_start[next];
+                if best.is_none_or(|b| ranges[idx].hi > ranges[b].hi) {
+                    best = Some(idx);
+                }
+                next += 1;
+            }
+
+            let Some(best) = best else { break };
+            result.push(best);
+            lo = ranges[best].hi.checked_add(1);
+        }
+    }
+
+    result.sort_unstable();
+    result
+}
+
+// All elves of a crew, written as comma separated ranges
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crew {
+    pub elves: Vec<Range>,
+}
+
+impl FromStr for Crew {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let elves = s.split(',').map(str::parse).collect::<Result<_, _>>()?;
+        Ok(Self { elves })
+    }
 }
 
-#[derive(Display, FromStr, Copy, Clone, Debug)]
-#[display("{lhs},{rhs}")]
-pub struct RangePair {
-    lhs: Range,
-    rhs: Range,
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrewReport {
+    pub covered_elves: Vec<usize>,
+    pub most_overlap: Option<(i64, usize)>,
+    pub minimum_cover: Vec<usize>,
+}
+
+impl Crew {
+    #[must_use]
+    pub fn report(&self) -> CrewReport {
+        CrewReport {
+            covered_elves: redundant_assignments(&self.elves),
+            most_overlap: most_overlap(&self.elves),
+            minimum_cover: minimum_cover(&self.elves),
+        }
+    }
 }
 
+#[must_use]
 #[aoc_generator(day4)]
-pub fn generate(inp: &str) -> Vec<RangePair> {
+pub fn generate(inp: &str) -> Vec<Crew> {
     inp.lines().filter_map(|it| it.parse().ok()).collect()
 }
 
+#[must_use]
 #[aoc(day4, part1)]
-pub fn part1(inp: &[RangePair]) -> usize {
+pub fn part1(inp: &[Crew]) -> usize {
     inp.iter()
-        .filter(|RangePair { lhs, rhs }| lhs.fully_contains(rhs) || rhs.fully_contains(lhs))
+        .filter(|it| !redundant_assignments(&it.elves).is_empty())
         .count()
 }
 
+#[must_use]
 #[aoc(day4, part2)]
-pub fn part2(inp: &[RangePair]) -> usize {
+pub fn part2(inp: &[Crew]) -> usize {
     inp.iter()
-        .filter(|RangePair { lhs, rhs }| lhs.overlaps(rhs))
+        .filter(|it| !covered_by_at_least(&it.elves, 2).is_empty())
         .count()
 }
 
@@ -73,4 +374,78 @@ mod tests {
         let res = part2(&data);
         assert_eq!(res, 4);
     }
+
+    fn set(ranges: &[(i64, i64)]) -> IntervalSet {
+        IntervalSet::from_ranges(ranges.iter().map(|&(lo, hi)| Range::new(lo, hi)))
+    }
+
+    #[test]
+    fn test_interval_set() {
+        let lhs = set(&[(5, 7), (1, 3), (4, 4), (10, 12)]);
+        assert_eq!(lhs, set(&[(1, 7), (10, 12)]));
+        assert_eq!(lhs.len(), 10);
+        assert!(lhs.contains(10));
+        assert!(!lhs.contains(8));
+
+        let rhs = set(&[(3, 5), (7, 11)]);
+        assert_eq!(lhs.union(&rhs), set(&[(1, 12)]));
+        assert_eq!(lhs.intersection(&rhs), set(&[(3, 5), (7, 7), (10, 11)]));
+        assert_eq!(lhs.difference(&rhs), set(&[(1, 2), (6, 6), (12, 12)]));
+        assert_eq!(rhs.difference(&lhs), set(&[(8, 9)]));
+        assert!(lhs.difference(&lhs).is_empty());
+    }
+
+    #[test]
+    fn test_coverage_queries() {
+        let ranges = [
+            Range::new(2, 8),
+            Range::new(3, 7),
+            Range::new(6, 9),
+            Range::new(1, 1),
+        ];
+
+        assert_eq!(covered_by_at_least(&ranges, 1), set(&[(1, 9)]));
+        assert_eq!(covered_by_at_least(&ranges, 2), set(&[(3, 8)]));
+        assert_eq!(covered_by_at_least(&ranges, 3), set(&[(6, 7)]));
+        assert!(covered_by_at_least(&ranges, 4).is_empty());
+
+        // Everything is covered by at least zero elves
+        let everything = covered_by_at_least(&[], 0);
+        assert!(everything.contains(i64::MIN) && everything.contains(i64::MAX));
+        assert_eq!(everything.len(), u64::MAX);
+        assert_eq!(Range::new(i64::MIN, i64::MAX).len(), u64::MAX);
+
+        assert_eq!(redundant_assignments(&ranges), vec![1]);
+        assert_eq!(
+            redundant_assignments(&[Range::new(1, 2), Range::new(1, 2)]),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn test_crew_report() {
+        let data = generate("2-8,3-7,6-9,1-1\n1-3,2-5,4-9,5-6,10-12");
+
+        assert_eq!(data[0].elves.len(), 4);
+        assert_eq!(
+            data[0].report(),
+            CrewReport {
+                covered_elves: vec![1],
+                most_overlap: Some((6, 3)),
+                minimum_cover: vec![0, 2, 3],
+            }
+        );
+
+        assert_eq!(
+            data[1].report(),
+            CrewReport {
+                covered_elves: vec![1, 3],
+                most_overlap: Some((5, 3)),
+                minimum_cover: vec![0, 2, 4],
+            }
+        );
+
+        assert_eq!(part1(&data), 2);
+        assert_eq!(part2(&data), 2);
+    }
 }
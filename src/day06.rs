@@ -1,49 +1,98 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Read};
 
+#[must_use]
 #[aoc_generator(day6)]
-pub fn generate(inp: &str) -> Vec<char> {
-    inp.chars().collect()
+pub fn generate(inp: &str) -> Vec<u8> {
+    inp.bytes().collect()
 }
 
-fn find_unique_window(data: &[char], size: usize) -> usize {
-    // Keep track of chars and their first occurrence
-    let mut seen = HashMap::new();
-
-    let mut idx = 0;
-    'outer: loop {
-        // Clear previous iteration
-        seen.clear();
-
-        for (collision_idx, c) in data[idx..idx + size].iter().enumerate() {
-            match seen.entry(c) {
-                // If we have seen this char already, move our idx until 1 after the first occurrence
-                // e.g. for "abcc" we don't have to check index 1, since the first collision
-                // was at indices 2 and 3
-                Entry::Occupied(e) => {
-                    idx += *e.get() + 1;
-                    continue 'outer;
-                }
-                // Insert & keep iterating
-                Entry::Vacant(n) => {
-                    n.insert(collision_idx);
-                }
+// Keeps track of the last `size` bytes and how many of them are repeated
+#[derive(Clone, Debug)]
+pub struct MarkerDetector {
+    size: usize,
+    window: VecDeque<u8>,
+    counts: [usize; 256],
+    duplicates: usize,
+    offset: usize,
+}
+
+impl MarkerDetector {
+    #[must_use]
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            window: VecDeque::with_capacity(size + 1),
+            counts: [0; 256],
+            duplicates: 0,
+            offset: 0,
+        }
+    }
+
+    // Returns the number of bytes processed so far, if the last `size` bytes are all different
+    pub fn push(&mut self, byte: u8) -> Option<usize> {
+        self.offset += 1;
+
+        self.window.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 2 {
+            self.duplicates += 1;
+        }
+
+        if self.window.len() > self.size {
+            let old = self.window.pop_front()?;
+            self.counts[old as usize] -= 1;
+            if self.counts[old as usize] == 1 {
+                self.duplicates -= 1;
             }
         }
 
-        return idx + size;
+        (self.window.len() == self.size && self.duplicates == 0).then_some(self.offset)
     }
 }
 
+pub struct Markers<I> {
+    bytes: I,
+    detector: MarkerDetector,
+}
+
+impl<I: Iterator<Item = u8>> Iterator for Markers<I> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.bytes.by_ref().find_map(|b| self.detector.push(b))
+    }
+}
+
+// Every offset at which a marker of `size` unique bytes ends
+pub fn find_markers<I: IntoIterator<Item = u8>>(bytes: I, size: usize) -> Markers<I::IntoIter> {
+    Markers {
+        bytes: bytes.into_iter(),
+        detector: MarkerDetector::new(size),
+    }
+}
+
+// Same as `find_markers`, but streams the bytes from a reader
+pub fn find_markers_in<R: Read>(reader: R, size: usize) -> impl Iterator<Item = io::Result<usize>> {
+    let mut detector = MarkerDetector::new(size);
+
+    BufReader::new(reader).bytes().filter_map(move |b| match b {
+        Ok(b) => detector.push(b).map(Ok),
+        Err(e) => Some(Err(e)),
+    })
+}
+
+#[must_use]
 #[aoc(day6, part1)]
-pub fn part1(inp: &[char]) -> usize {
-    find_unique_window(inp, 4)
+pub fn part1(inp: &[u8]) -> Option<usize> {
+    find_markers(inp.iter().copied(), 4).next()
 }
 
+#[must_use]
 #[aoc(day6, part2)]
-pub fn part2(inp: &[char]) -> usize {
-    find_unique_window(inp, 14)
+pub fn part2(inp: &[u8]) -> Option<usize> {
+    find_markers(inp.iter().copied(), 14).next()
 }
 
 #[cfg(test)]
@@ -62,7 +111,7 @@ mod tests {
         for (s, expected) in inp {
             let data = generate(s);
             let res = part1(&data);
-            assert_eq!(res, expected);
+            assert_eq!(res, Some(expected));
         }
     }
 
@@ -79,7 +128,31 @@ mod tests {
         for (s, expected) in inp {
             let data = generate(s);
             let res = part2(&data);
-            assert_eq!(res, expected);
+            assert_eq!(res, Some(expected));
         }
     }
+
+    #[test]
+    fn test_no_marker() {
+        let data = generate("aabbaabb");
+        assert_eq!(part1(&data), None);
+        assert_eq!(find_markers(data, 3).count(), 0);
+        assert_eq!(find_markers(vec![], 4).next(), None);
+    }
+
+    #[test]
+    fn test_all_markers() {
+        let markers = find_markers("abcabcaab".bytes(), 3).collect::<Vec<_>>();
+        assert_eq!(markers, vec![3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_markers_from_reader() {
+        let reader = &b"mjqjpqmgbljsphdztnvjfqwrcgsmlb"[..];
+        let first = find_markers_in(reader, 14)
+            .next()
+            .expect("Marker should exist")
+            .expect("Read should succeed");
+        assert_eq!(first, 19);
+    }
 }
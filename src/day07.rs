@@ -1,6 +1,8 @@
+use crate::day04::{IntervalSet, Range};
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
 use parse_display::{Display, FromStr};
-use std::collections::HashMap;
+use std::fmt;
 
 #[derive(Display, FromStr, Clone, Debug)]
 pub enum ChangeDir {
@@ -20,58 +22,294 @@ pub enum Command {
     Entry(usize, String),
 }
 
+#[must_use]
 #[aoc_generator(day7)]
 pub fn generate(inp: &str) -> Vec<Command> {
     inp.lines().filter_map(|it| it.parse().ok()).collect()
 }
 
-fn collect_command_output(cmds: &[Command]) -> HashMap<String, usize> {
-    let mut directory_stack: Vec<&str> = vec![];
+#[derive(Clone, Debug)]
+struct Directory {
+    path: String,
+    size: usize,
+    subdirs: Vec<usize>,
+}
+
+// Builds the directory tree, the root directory is always at index 0
+fn collect_directories(cmds: &[Command]) -> Vec<Directory> {
+    let mut dirs = vec![Directory {
+        path: "/".to_string(),
+        size: 0,
+        subdirs: vec![],
+    }];
 
-    let mut result = HashMap::new();
-    result.insert("/".to_string(), 0);
+    let mut directory_stack = vec![0];
 
     for cmd in cmds {
         match cmd {
             Command::CD(ChangeDir::Up) => {
-                directory_stack.pop();
+                if directory_stack.len() > 1 {
+                    directory_stack.pop();
+                }
+            }
+            Command::CD(ChangeDir::Subdir(dir_name)) if dir_name == "/" => {
+                directory_stack.truncate(1);
             }
             Command::CD(ChangeDir::Subdir(dir_name)) => {
-                directory_stack.push(dir_name);
+                let parent = directory_stack[directory_stack.len() - 1];
+                let path = if parent == 0 {
+                    format!("/{dir_name}")
+                } else {
+                    format!("{}/{dir_name}", dirs[parent].path)
+                };
+
+                let existing = dirs[parent]
+                    .subdirs
+                    .iter()
+                    .copied()
+                    .find(|&idx| dirs[idx].path == path);
+
+                let idx = existing.unwrap_or_else(|| {
+                    let idx = dirs.len();
+                    dirs.push(Directory {
+                        path,
+                        size: 0,
+                        subdirs: vec![],
+                    });
+                    dirs[parent].subdirs.push(idx);
+                    idx
+                });
+
+                directory_stack.push(idx);
             }
             Command::Entry(size, _) => {
                 // Just add the size to all parent directories as well
-                for idx in 0..directory_stack.len() {
-                    let cur_dir_name = directory_stack[..=idx].join("/");
-                    *result.entry(cur_dir_name).or_insert(0) += size;
+                for &idx in &directory_stack {
+                    dirs[idx].size += size;
                 }
             }
+        }
+    }
+
+    dirs
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CleanupGoal {
+    FewestBytes,
+    FewestDirectories,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CleanupPlan {
+    pub goal: CleanupGoal,
+    pub disk_size: usize,
+    pub used: usize,
+    pub needed: usize,
+    pub directories: Vec<(String, usize)>,
+}
+
+impl CleanupPlan {
+    #[must_use]
+    pub fn freed(&self) -> usize {
+        self.directories.iter().map(|(_, size)| size).sum()
+    }
+}
+
+impl fmt::Display for CleanupPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let free = self.disk_size.saturating_sub(self.used);
+        writeln!(
+            f,
+            "{} of {} bytes used, {free} free: need to delete at least {} bytes",
+            self.used, self.disk_size, self.needed
+        )?;
+
+        if self.directories.is_empty() {
+            return writeln!(f, "Nothing to delete");
+        }
+
+        let goal = match self.goal {
+            CleanupGoal::FewestBytes => "fewest bytes removed",
+            CleanupGoal::FewestDirectories => "fewest directories removed",
         };
+
+        writeln!(
+            f,
+            "Deleting {} director{} frees {} bytes ({} more than needed, chosen for {goal}):",
+            self.directories.len(),
+            if self.directories.len() == 1 {
+                "y"
+            } else {
+                "ies"
+            },
+            self.freed(),
+            self.freed() - self.needed
+        )?;
+
+        for (path, size) in &self.directories {
+            writeln!(f, "  {path} ({size})")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Directory indices in preorder, together with the position right after each subtree
+fn preorder(dirs: &[Directory]) -> (Vec<usize>, Vec<usize>) {
+    fn visit(dirs: &[Directory], idx: usize, order: &mut Vec<usize>, subtree_end: &mut [usize]) {
+        order.push(idx);
+        for &sub in &dirs[idx].subdirs {
+            visit(dirs, sub, order, subtree_end);
+        }
+        subtree_end[idx] = order.len();
     }
 
-    result
+    let mut order = vec![];
+    let mut subtree_end = vec![0; dirs.len()];
+
+    visit(dirs, 0, &mut order, &mut subtree_end);
+    (order, subtree_end)
 }
 
+// Finds the non-nested directories freeing the fewest bytes (at least `needed`, at most `limit`).
+//
+// Walking the tree in preorder, each directory is either deleted (skipping its whole subtree)
+// or kept (descending into it). Reachable sums are kept as intervals, so the cost depends on how
+// the directory sizes combine and not on the byte counts. The sums only ever grow along the
+// walk, so remembering which directory first added each interval is enough to reconstruct the
+// chosen directories.
+#[allow(clippy::cast_possible_wrap)]
+fn fewest_bytes(dirs: &[Directory], needed: usize, limit: usize) -> Option<Vec<usize>> {
+    let (order, subtree_end) = preorder(dirs);
+    let (needed, limit) = (i64::try_from(needed).ok()?, i64::try_from(limit).ok()?);
+    let size = |dir: usize| dirs[dir].size as i64;
+
+    let allowed = IntervalSet::from_ranges([Range::new(0, limit)]);
+    let mut reach = IntervalSet::from_ranges([Range::new(0, 0)]);
+    let mut added_by: Vec<(Range, usize)> = vec![];
+
+    // Sums reachable right before a directory, applied once its subtree has been walked
+    let mut pending: Vec<(usize, IntervalSet)> = vec![];
+
+    for pos in 0..=order.len() {
+        while let Some((dir, before)) = pending.last() {
+            if subtree_end[*dir] != pos {
+                break;
+            }
+
+            let shifted = IntervalSet::from_ranges(before.ranges().iter().map(|it| {
+                Range::new(
+                    it.lo().saturating_add(size(*dir)),
+                    it.hi().saturating_add(size(*dir)),
+                )
+            }))
+            .intersection(&allowed);
+
+            let added = shifted.difference(&reach);
+            added_by.extend(added.ranges().iter().map(|it| (*it, *dir)));
+            reach = reach.union(&added);
+            pending.pop();
+        }
+
+        if let Some(&dir) = order.get(pos) {
+            if size(dir) <= limit {
+                pending.push((dir, reach.clone()));
+            }
+        }
+    }
+
+    let mut sum = reach
+        .ranges()
+        .iter()
+        .find(|it| it.hi() >= needed)
+        .map(|it| it.lo().max(needed))
+        .filter(|it| *it <= limit)?;
+
+    added_by.sort_unstable();
+
+    let mut result = vec![];
+    while sum > 0 {
+        let idx = added_by.partition_point(|(range, _)| range.hi() < sum);
+        let (_, dir) = added_by[idx];
+        result.push(dir);
+        sum -= size(dir);
+    }
+
+    Some(result)
+}
+
+#[must_use]
+pub fn plan_cleanup(
+    cmds: &[Command],
+    disk_size: usize,
+    space_needed: usize,
+    goal: CleanupGoal,
+) -> Option<CleanupPlan> {
+    let dirs = collect_directories(cmds);
+
+    let used = dirs[0].size;
+    let needed = space_needed.saturating_sub(disk_size.saturating_sub(used));
+
+    if needed > used {
+        return None;
+    }
+
+    let directories = if needed == 0 {
+        vec![]
+    } else {
+        // A single directory is always enough (at worst the root), so this is also the
+        // upper bound for any combination of directories
+        let smallest_single = (0..dirs.len())
+            .filter(|&idx| dirs[idx].size >= needed)
+            .min_by_key(|&idx| dirs[idx].size)?;
+
+        let chosen = match goal {
+            CleanupGoal::FewestDirectories => vec![smallest_single],
+            CleanupGoal::FewestBytes => fewest_bytes(&dirs, needed, dirs[smallest_single].size)?,
+        };
+
+        let mut directories = chosen
+            .into_iter()
+            .map(|idx| (dirs[idx].path.clone(), dirs[idx].size))
+            .collect_vec();
+        directories.sort();
+        directories
+    };
+
+    Some(CleanupPlan {
+        goal,
+        disk_size,
+        used,
+        needed,
+        directories,
+    })
+}
+
+#[must_use]
 #[aoc(day7, part1)]
 pub fn part1(cmds: &[Command]) -> usize {
-    let dirs = collect_command_output(cmds);
-    dirs.values().filter(|it| **it <= 100_000).sum()
+    let dirs = collect_directories(cmds);
+    dirs.iter()
+        .map(|it| it.size)
+        .filter(|it| *it <= 100_000)
+        .sum()
 }
 
+#[must_use]
 #[aoc(day7, part2)]
 pub fn part2(cmds: &[Command]) -> Option<usize> {
     const TOTAL_SIZE: usize = 70_000_000;
     const EMPTY_NEEDED: usize = 30_000_000;
 
-    let dirs = collect_command_output(cmds);
-
-    let cur_size = *dirs.get("/")?;
-    let needed_cleanup = EMPTY_NEEDED - (TOTAL_SIZE - cur_size);
-
-    dirs.values()
-        .filter(|it| **it >= needed_cleanup)
-        .min()
-        .copied()
+    // The smallest single directory is the cheapest plan among those deleting the fewest directories
+    let plan = plan_cleanup(
+        cmds,
+        TOTAL_SIZE,
+        EMPTY_NEEDED,
+        CleanupGoal::FewestDirectories,
+    )?;
+    Some(plan.freed())
 }
 
 #[cfg(test)]
@@ -115,4 +353,103 @@ mod tests {
         let res = part2(&data);
         assert_eq!(res, Some(24_933_642));
     }
+
+    #[test]
+    fn test_plan_fewest_bytes() {
+        let data = generate(TEST_DATA);
+
+        // 48_381_165 used, so 1_618_835 free and 'd' alone is one byte short
+        let plan = plan_cleanup(&data, 50_000_000, 26_552_478, CleanupGoal::FewestBytes)
+            .expect("Plan should exist");
+        assert_eq!(plan.needed, 24_933_643);
+        assert_eq!(plan.freed(), 24_934_226);
+        assert_eq!(
+            plan.directories,
+            vec![("/a/e".to_string(), 584), ("/d".to_string(), 24_933_642)]
+        );
+
+        let plan = plan_cleanup(
+            &data,
+            50_000_000,
+            26_552_478,
+            CleanupGoal::FewestDirectories,
+        )
+        .expect("Plan should exist");
+        assert_eq!(plan.directories, vec![("/".to_string(), 48_381_165)]);
+    }
+
+    #[test]
+    fn test_plan_fewest_directories() {
+        let data = generate(TEST_DATA);
+        let plan = plan_cleanup(
+            &data,
+            70_000_000,
+            30_000_000,
+            CleanupGoal::FewestDirectories,
+        )
+        .expect("Plan should exist");
+        assert_eq!(plan.directories, vec![("/d".to_string(), 24_933_642)]);
+    }
+
+    #[test]
+    fn test_plan_large_disk() {
+        const GB: usize = 1_000_000_000;
+
+        let data = generate(&format!(
+            "$ cd /\n\
+             {} boot.img\n\
+             $ cd media\n\
+             {} movie.mkv\n\
+             $ cd photos\n\
+             {} raw.tar\n\
+             $ cd ..\n\
+             $ cd ..\n\
+             $ cd logs\n\
+             {} old.log\n\
+             $ cd ..\n\
+             $ cd cache\n\
+             {} blob",
+            GB / 2,
+            GB + 300_000_000,
+            700_000_000,
+            900_000_001,
+            600_000_000
+        ));
+
+        // Just over 4 GB used on a 6 GB disk, so just over 0.5 GB has to go
+        let plan = plan_cleanup(&data, 6 * GB, 2 * GB + GB / 2, CleanupGoal::FewestBytes)
+            .expect("Plan should exist");
+        assert_eq!(plan.needed, GB / 2 + 1);
+        assert_eq!(plan.directories, vec![("/cache".to_string(), 600_000_000)]);
+
+        // 1.4 GB has to go: logs and cache together beat media on its own
+        let plan = plan_cleanup(
+            &data,
+            6 * GB,
+            3 * GB + 400_000_000,
+            CleanupGoal::FewestBytes,
+        )
+        .expect("Plan should exist");
+        assert_eq!(plan.needed, 1_400_000_001);
+        assert_eq!(plan.freed(), 1_500_000_001);
+        assert_eq!(
+            plan.directories,
+            vec![
+                ("/cache".to_string(), 600_000_000),
+                ("/logs".to_string(), 900_000_001)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_edge_cases() {
+        let data = generate(TEST_DATA);
+
+        let plan = plan_cleanup(&data, 100_000_000, 10_000_000, CleanupGoal::FewestBytes)
+            .expect("Plan should exist");
+        assert!(plan.directories.is_empty());
+        assert!(plan.to_string().contains("Nothing to delete"));
+
+        assert!(plan_cleanup(&data, 40_000_000, 50_000_000, CleanupGoal::FewestBytes).is_none());
+    }
 }
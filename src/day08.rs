@@ -1,10 +1,14 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::FoldWhile::{Continue, Done};
 use itertools::Itertools;
 use pathfinding::prelude::Matrix;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
 
 const DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONALS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
 
+#[must_use]
 #[aoc_generator(day8)]
 pub fn generate(inp: &str) -> Option<Matrix<u32>> {
     let rows = inp
@@ -15,46 +19,274 @@ pub fn generate(inp: &str) -> Option<Matrix<u32>> {
     Matrix::from_rows(rows).ok()
 }
 
-fn is_tree_visible((y, x): (usize, usize), inp: &Matrix<u32>) -> bool {
-    // edges always visible
-    if y == 0 || x == 0 || y == inp.rows || x == inp.columns {
-        return true;
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sightlines {
+    Orthogonal,
+    WithDiagonals,
+}
+
+impl Sightlines {
+    fn directions(self) -> Vec<(isize, isize)> {
+        match self {
+            Self::Orthogonal => DIRECTIONS.to_vec(),
+            Self::WithDiagonals => DIRECTIONS.iter().chain(&DIAGONALS).copied().collect(),
+        }
     }
+}
+
+// Walks every line of trees in `dir`, starting at the edge. For each tree `visit` gets the
+// closest earlier tree that is at least as tall, if any.
+//
+// The stack holds the earlier trees not yet blocked by a taller one, so heights on it never
+// increase and every tree is pushed and popped at most once per line.
+fn sweep(
+    inp: &Matrix<u32>,
+    dir: (isize, isize),
+    mut visit: impl FnMut((usize, usize), Option<(usize, usize)>),
+) {
+    let back = (-dir.0, -dir.1);
+    let mut stack = vec![];
 
-    let current = inp[(y, x)];
-    DIRECTIONS.iter().fold(0, |acc, it| {
-        acc + usize::from(inp.in_direction((y, x), *it).all(|p| inp[p] < current))
-    }) > 0
+    for start in inp
+        .keys()
+        .filter(|it| inp.move_in_direction(*it, back).is_none())
+    {
+        stack.clear();
+
+        for pos in std::iter::once(start).chain(inp.in_direction(start, dir)) {
+            while stack.last().is_some_and(|it| inp[*it] < inp[pos]) {
+                stack.pop();
+            }
+
+            visit(pos, stack.last().copied());
+            stack.push(pos);
+        }
+    }
 }
 
-fn scenic_score(pos: (usize, usize), inp: &Matrix<u32>) -> usize {
-    let current = inp[pos];
+// Whether each tree can be seen from outside the forest along any of the sightlines
+#[must_use]
+pub fn visibility_map(inp: &Matrix<u32>, sightlines: Sightlines) -> Matrix<bool> {
+    let mut visible = Matrix::new(inp.rows, inp.columns, false);
 
-    DIRECTIONS
+    for dir in sightlines.directions() {
+        sweep(inp, dir, |pos, blocker| {
+            visible[pos] |= blocker.is_none();
+        });
+    }
+
+    visible
+}
+
+#[must_use]
+pub fn render_visibility(visible: &Matrix<bool>) -> String {
+    visible
         .iter()
-        .map(|it| {
-            inp.in_direction(pos, *it)
-                .fold_while(0, |inner, p| {
-                    if inp[p] < current {
-                        Continue(inner + 1)
+        .map(|row| {
+            row.iter()
+                .map(|it| if *it { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .join("\n")
+}
+
+fn steps_to_edge(inp: &Matrix<u32>, (row, col): (usize, usize), (dr, dc): (isize, isize)) -> usize {
+    let remaining = |at: usize, size: usize, d: isize| match d {
+        -1 => at,
+        1 => size - 1 - at,
+        _ => usize::MAX,
+    };
+
+    remaining(row, inp.rows, dr).min(remaining(col, inp.columns, dc))
+}
+
+// How many trees can be seen from every tree looking in each of `DIRECTIONS` (up, down, left,
+// right). The blocking tree is the closest earlier one in a sweep from the opposite side.
+#[must_use]
+pub fn viewing_distances(inp: &Matrix<u32>) -> [Matrix<usize>; 4] {
+    DIRECTIONS.map(|look| {
+        let mut distances = Matrix::new(inp.rows, inp.columns, 0);
+
+        sweep(inp, (-look.0, -look.1), |pos, blocker| {
+            distances[pos] = blocker.map_or_else(
+                || steps_to_edge(inp, pos, look),
+                |it| pos.0.abs_diff(it.0).max(pos.1.abs_diff(it.1)),
+            );
+        });
+
+        distances
+    })
+}
+
+#[must_use]
+pub fn scenic_scores(inp: &Matrix<u32>) -> Matrix<usize> {
+    let [up, down, left, right] = viewing_distances(inp);
+
+    let mut scores = up;
+    for pos in inp.keys() {
+        scores[pos] *= down[pos] * left[pos] * right[pos];
+    }
+    scores
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Site {
+    pub pos: (usize, usize),
+    pub height: u32,
+    pub score: usize,
+    // Viewing distances up, down, left and right
+    pub distances: [usize; 4],
+}
+
+impl fmt::Display for Site {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [up, down, left, right] = self.distances;
+        write!(
+            f,
+            "{:?} height {}: score {} (up {up}, down {down}, left {left}, right {right})",
+            self.pos, self.height, self.score
+        )
+    }
+}
+
+// The `k` best treehouse sites, highest score first and ties in reading order
+#[must_use]
+pub fn best_sites(inp: &Matrix<u32>, k: usize) -> Vec<Site> {
+    let distances = viewing_distances(inp);
+    let scores = scenic_scores(inp);
+
+    inp.keys()
+        .sorted_by_key(|it| (Reverse(scores[*it]), *it))
+        .take(k)
+        .map(|pos| Site {
+            pos,
+            height: inp[pos],
+            score: scores[pos],
+            distances: [0, 1, 2, 3].map(|dir| distances[dir][pos]),
+        })
+        .collect()
+}
+
+// Whether a tree blocks the sightline from an eye at `eye` above `observer` to the top of `target`.
+//
+// Trees are solid columns filling their cell. Walking the segment between the two cell centers,
+// the sightline is blocked if it is not strictly above a tree where it enters or leaves its
+// cell (it is straight, so those are its lowest points over the cell). Passing exactly through
+// a corner touches both cells beside it, so diagonal walls have no gaps.
+#[allow(clippy::cast_possible_wrap)]
+fn is_hidden(
+    inp: &Matrix<u32>,
+    observer: (usize, usize),
+    eye: i64,
+    target: (usize, usize),
+) -> bool {
+    let top = i64::from(inp[target]);
+    let (a, b) = (
+        target.0.abs_diff(observer.0) as i64,
+        target.1.abs_diff(observer.1) as i64,
+    );
+    let dr = if target.0 > observer.0 { 1 } else { -1 };
+    let dc = if target.1 > observer.1 { 1 } else { -1 };
+
+    let step = |(row, col): (usize, usize), dr: isize, dc: isize| {
+        (row.wrapping_add_signed(dr), col.wrapping_add_signed(dc))
+    };
+
+    // At `num / den` of the way, the sightline is at `eye + num / den * (top - eye)`
+    let below = |cell: (usize, usize), num: i64, den: i64| {
+        eye * den + num * (top - eye) <= i64::from(inp[cell]) * den
+    };
+
+    let mut cell = observer;
+    let (mut k, mut j) = (1, 1);
+
+    while k <= a || j <= b {
+        // The k-th row boundary is crossed at (2k - 1) / 2a, the j-th column one at (2j - 1) / 2b
+        let row_next = j > b || (k <= a && (2 * k - 1) * b <= (2 * j - 1) * a);
+        let col_next = k > a || (j <= b && (2 * j - 1) * a <= (2 * k - 1) * b);
+        let (num, den) = if row_next {
+            (2 * k - 1, 2 * a)
+        } else {
+            (2 * j - 1, 2 * b)
+        };
+
+        if cell != observer && below(cell, num, den) {
+            return true;
+        }
+
+        if row_next && col_next {
+            if below(step(cell, dr, 0), num, den) || below(step(cell, 0, dc), num, den) {
+                return true;
+            }
+            cell = step(cell, dr, dc);
+            k += 1;
+            j += 1;
+        } else if row_next {
+            cell = step(cell, dr, 0);
+            k += 1;
+        } else {
+            cell = step(cell, 0, dc);
+            j += 1;
+        }
+
+        if cell != target && below(cell, num, den) {
+            return true;
+        }
+    }
+
+    false
+}
+
+// Every tree whose top can be seen from an eye at `height` above the center of `observer`,
+// along a straight sightline over the trees in between (see `is_hidden`)
+#[must_use]
+pub fn line_of_sight(
+    inp: &Matrix<u32>,
+    observer: (usize, usize),
+    height: u32,
+) -> HashSet<(usize, usize)> {
+    inp.keys()
+        .filter(|it| *it != observer && !is_hidden(inp, observer, i64::from(height), *it))
+        .collect()
+}
+
+// Heights of the visible trees, '.' for hidden ones and 'O' for the observer
+#[must_use]
+pub fn render_sight(
+    inp: &Matrix<u32>,
+    observer: (usize, usize),
+    visible: &HashSet<(usize, usize)>,
+) -> String {
+    (0..inp.rows)
+        .map(|row| {
+            (0..inp.columns)
+                .map(|col| {
+                    if (row, col) == observer {
+                        'O'
+                    } else if visible.contains(&(row, col)) {
+                        char::from_digit(inp[(row, col)], 10).unwrap_or('#')
                     } else {
-                        Done(inner + 1)
+                        '.'
                     }
                 })
-                .into_inner()
+                .collect::<String>()
         })
-        .product()
+        .join("\n")
 }
 
+#[must_use]
 #[aoc(day8, part1)]
 pub fn part1(inp: &Matrix<u32>) -> usize {
-    inp.keys()
-        .fold(0, |acc, it| acc + usize::from(is_tree_visible(it, inp)))
+    visibility_map(inp, Sightlines::Orthogonal)
+        .values()
+        .filter(|it| **it)
+        .count()
 }
 
+#[must_use]
 #[aoc(day8, part2)]
 pub fn part2(inp: &Matrix<u32>) -> Option<usize> {
-    inp.keys().map(|it| scenic_score(it, inp)).max()
+    scenic_scores(inp).values().max().copied()
 }
 
 #[cfg(test)]
@@ -77,7 +309,7 @@ mod tests {
     #[test]
     fn test_score() {
         let data = generate(TEST_INPUT).expect("Failed generating test input");
-        let score = scenic_score((1, 2), &data);
+        let score = scenic_scores(&data)[(1, 2)];
         assert_eq!(score, 4);
     }
 
@@ -87,4 +319,204 @@ mod tests {
         let res = part2(&data);
         assert_eq!(res, Some(8));
     }
+
+    #[test]
+    fn test_visibility_map() {
+        let data = generate(TEST_INPUT).expect("Failed generating test input");
+        let visible = visibility_map(&data, Sightlines::Orthogonal);
+
+        let expected = "#####\n\
+                        ###.#\n\
+                        ##.##\n\
+                        #.#.#\n\
+                        #####";
+        assert_eq!(render_visibility(&visible), expected);
+    }
+
+    #[test]
+    fn test_visibility_with_diagonals() {
+        let data = generate(TEST_INPUT).expect("Failed generating test input");
+        let visible = visibility_map(&data, Sightlines::WithDiagonals);
+
+        // Straightforward check, walking outwards from every tree
+        for pos in data.keys() {
+            let expected = DIRECTIONS
+                .iter()
+                .chain(&DIAGONALS)
+                .any(|dir| data.in_direction(pos, *dir).all(|it| data[it] < data[pos]));
+            assert_eq!(visible[pos], expected, "{pos:?}");
+        }
+
+        // The 4 is hidden along rows and columns, but not from the top right
+        assert!(visible[(3, 3)]);
+        assert!(!visible[(2, 2)]);
+
+        let single = generate("5").expect("Failed generating test input");
+        assert_eq!(
+            render_visibility(&visibility_map(&single, Sightlines::Orthogonal)),
+            "#"
+        );
+    }
+
+    #[test]
+    fn test_viewing_distances() {
+        let data = generate(TEST_INPUT).expect("Failed generating test input");
+        let [up, down, left, right] = viewing_distances(&data);
+
+        assert_eq!(
+            [up[(3, 2)], down[(3, 2)], left[(3, 2)], right[(3, 2)]],
+            [2, 1, 2, 2]
+        );
+        assert_eq!(
+            [up[(0, 0)], down[(0, 0)], left[(0, 0)], right[(0, 0)]],
+            [0, 2, 0, 2]
+        );
+        assert_eq!(right[(1, 0)], 1);
+        assert_eq!(down[(0, 4)], 3);
+
+        // Straightforward check, walking outwards from every tree until the view is blocked
+        for (dir, distances) in DIRECTIONS.iter().zip([up, down, left, right]) {
+            for pos in data.keys() {
+                let trees = data.in_direction(pos, *dir).collect_vec();
+                let expected = trees
+                    .iter()
+                    .position(|it| data[*it] >= data[pos])
+                    .map_or(trees.len(), |it| it + 1);
+                assert_eq!(distances[pos], expected, "{pos:?} {dir:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_best_sites() {
+        let data = generate(TEST_INPUT).expect("Failed generating test input");
+        let sites = best_sites(&data, 3);
+
+        assert_eq!(
+            sites.iter().map(|it| (it.pos, it.score)).collect_vec(),
+            vec![((3, 2), 8), ((2, 1), 6), ((1, 2), 4)]
+        );
+        assert_eq!(
+            sites[0].to_string(),
+            "(3, 2) height 5: score 8 (up 2, down 1, left 2, right 2)"
+        );
+        assert_eq!(best_sites(&data, 100).len(), 25);
+    }
+
+    #[test]
+    fn test_line_of_sight_flat() {
+        let flat = generate("00000\n00000\n00000\n00000\n00000").expect("Valid forest");
+
+        // Looking down on flat ground, everything is in sight
+        assert_eq!(line_of_sight(&flat, (0, 0), 1).len(), 24);
+
+        // At ground level, only the trees right next to the observer
+        let visible = line_of_sight(&flat, (2, 2), 0);
+        assert_eq!(
+            render_sight(&flat, (2, 2), &visible),
+            ".....\n\
+             ..0..\n\
+             .0O0.\n\
+             ..0..\n\
+             ....."
+        );
+    }
+
+    #[test]
+    fn test_line_of_sight_wall() {
+        let forest = generate("00000\n09990\n00000").expect("Valid forest");
+
+        // A solid wall hides everything behind it, even along the diagonals past its ends. The
+        // wall's ends are hidden too, the sightline to their tops grazes the middle tree's corner.
+        let below = line_of_sight(&forest, (0, 2), 5);
+        assert_eq!(
+            render_sight(&forest, (0, 2), &below),
+            "00O00\n\
+             ..9..\n\
+             ....."
+        );
+
+        let above = line_of_sight(&forest, (0, 2), 40);
+        assert_eq!(above.len(), 14);
+        assert!(below.is_subset(&above));
+
+        // Diagonal walls have no gaps either
+        let diagonal = generate("0000\n0900\n0090\n0000").expect("Valid forest");
+        let visible = line_of_sight(&diagonal, (0, 3), 1);
+        assert!(!visible.contains(&(3, 0)));
+        assert!(visible.contains(&(2, 2)) && visible.contains(&(1, 1)));
+    }
+
+    // Sightline hidden by any tree whose cell the segment touches, checking every cell
+    fn is_hidden_by_any(
+        inp: &Matrix<u32>,
+        observer: (usize, usize),
+        eye: i64,
+        target: (usize, usize),
+    ) -> bool {
+        #[allow(clippy::cast_possible_wrap)]
+        let coords = |(row, col): (usize, usize)| (row as i64, col as i64);
+        let ((r0, c0), (r1, c1)) = (coords(observer), coords(target));
+        let top = i64::from(inp[target]);
+
+        // Fractions as (num, den) with den > 0
+        let less = |(a, b): (i64, i64), (c, d): (i64, i64)| a * d < c * b;
+        let below =
+            |(num, den): (i64, i64), height: i64| eye * den + num * (top - eye) <= height * den;
+
+        inp.keys()
+            .filter(|it| *it != observer && *it != target)
+            .any(|cell| {
+                let (mut lo, mut hi) = ((0, 1), (1, 1));
+                let (r, c) = coords(cell);
+
+                for (from, delta, at) in [(r0, r1 - r0, r), (c0, c1 - c0, c)] {
+                    if delta == 0 {
+                        if at != from {
+                            return false;
+                        }
+                        continue;
+                    }
+
+                    // Inside the cell between (2 (at - from) -+ 1) / 2 delta
+                    let (den, sign) = (2 * delta.abs(), delta.signum());
+                    let (mut t1, mut t2) = (
+                        ((2 * (at - from) - 1) * sign, den),
+                        ((2 * (at - from) + 1) * sign, den),
+                    );
+                    if less(t2, t1) {
+                        std::mem::swap(&mut t1, &mut t2);
+                    }
+                    if less(lo, t1) {
+                        lo = t1;
+                    }
+                    if less(t2, hi) {
+                        hi = t2;
+                    }
+                }
+
+                let height = i64::from(inp[cell]);
+                !less(hi, lo) && (below(lo, height) || below(hi, height))
+            })
+    }
+
+    #[test]
+    fn test_line_of_sight_matches_brute_force() {
+        let data = generate(TEST_INPUT).expect("Failed generating test input");
+
+        for (observer, height) in [
+            ((3, 2), 5),
+            ((0, 0), 3),
+            ((2, 2), 7),
+            ((4, 4), 0),
+            ((1, 3), 9),
+        ] {
+            let visible = line_of_sight(&data, observer, height);
+
+            for pos in data.keys().filter(|it| *it != observer) {
+                let expected = !is_hidden_by_any(&data, observer, i64::from(height), pos);
+                assert_eq!(visible.contains(&pos), expected, "{observer:?} {pos:?}");
+            }
+        }
+    }
 }
@@ -1,69 +1,78 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::HashSet;
+use itertools::Itertools;
+use parse_display::{Display, FromStr};
+use std::collections::{HashMap, HashSet};
 use std::ops::AddAssign;
 
-#[derive(Copy, Clone, Debug)]
+use crate::day04::{IntervalSet, Range};
+
+#[derive(Display, FromStr, Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Direction {
+    #[display("U")]
     Up,
 
+    #[display("D")]
     Down,
 
+    #[display("L")]
     Left,
 
+    #[display("R")]
     Right,
+
+    #[display("UL")]
+    UpLeft,
+
+    #[display("UR")]
+    UpRight,
+
+    #[display("DL")]
+    DownLeft,
+
+    #[display("DR")]
+    DownRight,
 }
 
 impl Direction {
-    fn evaluate(self, knots: &mut [Pos]) {
+    const fn delta(self) -> Pos {
         match self {
-            Self::Up => {
-                knots[0].row -= 1;
-            }
-            Self::Down => {
-                knots[0].row += 1;
-            }
-            Self::Left => {
-                knots[0].col -= 1;
-            }
-            Self::Right => {
-                knots[0].col += 1;
-            }
+            Self::Up => Pos { row: -1, col: 0 },
+            Self::Down => Pos { row: 1, col: 0 },
+            Self::Left => Pos { row: 0, col: -1 },
+            Self::Right => Pos { row: 0, col: 1 },
+            Self::UpLeft => Pos { row: -1, col: -1 },
+            Self::UpRight => Pos { row: -1, col: 1 },
+            Self::DownLeft => Pos { row: 1, col: -1 },
+            Self::DownRight => Pos { row: 1, col: 1 },
         }
     }
 }
 
-#[aoc_generator(day9)]
-pub fn generate(inp: &str) -> Vec<Direction> {
-    inp.lines()
-        .flat_map(|it| {
-            let dir = it.chars().next().expect("Invalid input");
-            let steps = it[2..].parse::<usize>().expect("Invalid input");
-
-            let dir = match dir {
-                'U' => Direction::Up,
-                'D' => Direction::Down,
-                'L' => Direction::Left,
-                'R' => Direction::Right,
-                _ => unreachable!("Unhandled"),
-            };
+#[derive(Display, FromStr, Copy, Clone, Debug, PartialEq, Eq)]
+#[display("{dir} {steps}")]
+pub struct Instruction {
+    pub dir: Direction,
+    pub steps: usize,
+}
 
-            std::iter::repeat(dir).take(steps)
-        })
-        .collect()
+#[must_use]
+#[aoc_generator(day9)]
+pub fn generate(inp: &str) -> Option<Vec<Instruction>> {
+    inp.lines().map(|it| it.parse().ok()).collect()
 }
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
-struct Pos {
-    row: i32,
-    col: i32,
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
+pub struct Pos {
+    pub row: i64,
+    pub col: i64,
 }
 
 impl Pos {
-    const fn is_neighbour_of(self, other: Self) -> bool {
+    const fn is_within(self, other: Self, slack: u32) -> bool {
         let dx = self.col.abs_diff(other.col);
         let dy = self.row.abs_diff(other.row);
 
-        dx <= 1 && dy <= 1
+        dx <= slack as u64 && dy <= slack as u64
     }
 }
 
@@ -77,48 +86,404 @@ impl AddAssign for Pos {
 impl Pos {
     fn follow_direction(self, rhs: Self) -> Self {
         Self {
-            row: self.row.cmp(&rhs.row) as i32,
-            col: self.col.cmp(&rhs.col) as i32,
+            row: self.row.cmp(&rhs.row) as i64,
+            col: self.col.cmp(&rhs.col) as i64,
         }
     }
 }
 
-fn update_tail_knots(knots: &mut [Pos], tail_seen: &mut HashSet<Pos>) {
-    for knot_idx in 1..knots.len() {
-        let prev_knot = &knots[knot_idx - 1];
-        if knots[knot_idx].is_neighbour_of(*prev_knot) {
-            return;
+#[derive(Clone, Debug)]
+pub struct Rope {
+    pub knots: Vec<Pos>,
+    // `slack[i]` is how far knot `i + 1` may be from knot `i` before it has to follow
+    slack: Vec<u32>,
+    pinned: Vec<bool>,
+}
+
+impl Rope {
+    #[must_use]
+    pub fn new(num_knots: usize) -> Self {
+        Self {
+            knots: vec![Pos::default(); num_knots],
+            slack: vec![1; num_knots.saturating_sub(1)],
+            pinned: vec![false; num_knots],
+        }
+    }
+
+    #[must_use]
+    pub fn with_slack(mut self, segment: usize, slack: u32) -> Self {
+        self.slack[segment] = slack;
+        self
+    }
+
+    #[must_use]
+    pub fn with_uniform_slack(mut self, slack: u32) -> Self {
+        self.slack.fill(slack);
+        self
+    }
+
+    // A pinned knot never moves, the knots behind it still follow it
+    #[must_use]
+    pub fn with_pinned(mut self, knot: usize) -> Self {
+        self.pinned[knot] = true;
+        self
+    }
+
+    #[must_use]
+    pub fn tail(&self) -> Pos {
+        self.knots[self.knots.len() - 1]
+    }
+
+    // Moves the head one step, every other knot follows the one before it.
+    // Returns true if more steps in the same direction would just move the same knots the same way,
+    // i.e. the rope is straight up to the tail or a pinned knot.
+    fn step(&mut self, dir: Direction) -> bool {
+        if self.pinned[0] {
+            return true;
+        }
+
+        self.knots[0] += dir.delta();
+        let mut straight = true;
+
+        for knot_idx in 1..self.knots.len() {
+            if self.pinned[knot_idx] {
+                return straight;
+            }
+
+            let prev_knot = self.knots[knot_idx - 1];
+            if self.knots[knot_idx].is_within(prev_knot, self.slack[knot_idx - 1]) {
+                // The remaining knots can't move either
+                return false;
+            }
+
+            let diff = prev_knot.follow_direction(self.knots[knot_idx]);
+            self.knots[knot_idx] += diff;
+            straight &= diff == dir.delta();
         }
 
-        let diff = prev_knot.follow_direction(knots[knot_idx]);
-        knots[knot_idx] += diff;
+        straight
+    }
+
+    // Moves every knot in front of the first pinned one `times` steps, returns whether the tail moved
+    fn translate(&mut self, dir: Direction, times: i64) -> bool {
+        let delta = dir.delta();
+
+        for knot_idx in 0..self.knots.len() {
+            if self.pinned[knot_idx] {
+                return false;
+            }
+
+            self.knots[knot_idx] += Pos {
+                row: delta.row * times,
+                col: delta.col * times,
+            };
+        }
+
+        true
+    }
+}
+
+// A straight line in one of the eight directions, `a * row + b * col = k`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Line {
+    a: i64,
+    b: i64,
+    k: i64,
+}
+
+impl Line {
+    const fn through(pos: Pos, dir: Direction) -> Self {
+        let (a, b) = match dir {
+            Direction::Left | Direction::Right => (1, 0),
+            Direction::Up | Direction::Down => (0, 1),
+            Direction::UpLeft | Direction::DownRight => (-1, 1),
+            Direction::UpRight | Direction::DownLeft => (1, 1),
+        };
+
+        Self {
+            a,
+            b,
+            k: a * pos.row + b * pos.col,
+        }
+    }
+
+    const fn all_through(pos: Pos) -> [Self; 4] {
+        [
+            Self::through(pos, Direction::Right),
+            Self::through(pos, Direction::Up),
+            Self::through(pos, Direction::DownRight),
+            Self::through(pos, Direction::UpRight),
+        ]
+    }
+
+    // Where along the line `pos` is, rows only stay put on horizontal lines
+    const fn param(self, pos: Pos) -> i64 {
+        if self.b == 0 {
+            pos.col
+        } else {
+            pos.row
+        }
+    }
+
+    fn intersection(self, other: Self) -> Option<Pos> {
+        let det = self.a * other.b - other.a * self.b;
+        if det == 0 {
+            return None;
+        }
+
+        let row = self.k * other.b - other.k * self.b;
+        let col = self.a * other.k - other.a * self.k;
+
+        (row % det == 0 && col % det == 0).then(|| Pos {
+            row: row / det,
+            col: col / det,
+        })
+    }
+}
+
+// Cells visited by a knot. Long straight moves are kept as line segments, so huge moves
+// don't need one entry per cell.
+#[derive(Clone, Debug, Default)]
+pub struct Trail {
+    cells: HashSet<Pos>,
+    segments: HashMap<Line, IntervalSet>,
+}
+
+impl Trail {
+    fn insert(&mut self, pos: Pos) {
+        self.cells.insert(pos);
+    }
+
+    // Inserts the `len` cells after `from` in direction `dir`
+    fn insert_segment(&mut self, from: Pos, dir: Direction, len: i64) {
+        let line = Line::through(from, dir);
+        let delta = dir.delta();
+        let to = Pos {
+            row: from.row + delta.row * len,
+            col: from.col + delta.col * len,
+        };
+
+        let (start, end) = (line.param(from), line.param(to));
+        let segment = if start < end {
+            Range::new(start + 1, end)
+        } else {
+            Range::new(end, start - 1)
+        };
+
+        let merged = self.segments.get(&line).map_or_else(
+            || IntervalSet::from_ranges([segment]),
+            |it| it.union(&IntervalSet::from_ranges([segment])),
+        );
+        self.segments.insert(line, merged);
+    }
+
+    fn segment_coverage(&self, pos: Pos) -> usize {
+        Line::all_through(pos)
+            .into_iter()
+            .filter(|line| {
+                self.segments
+                    .get(line)
+                    .is_some_and(|it| it.contains(line.param(pos)))
+            })
+            .count()
+    }
+
+    #[must_use]
+    pub fn contains(&self, pos: Pos) -> bool {
+        self.cells.contains(&pos) || self.segment_coverage(pos) > 0
+    }
+
+    pub fn len(&self) -> usize {
+        let on_segments = self.segments.values().map(IntervalSet::len).sum::<u64>() as usize;
+
+        let lone_cells = self
+            .cells
+            .iter()
+            .filter(|it| self.segment_coverage(**it) == 0)
+            .count();
+
+        // Cells where segments cross were counted once per segment
+        let crossings = self
+            .segments
+            .keys()
+            .tuple_combinations()
+            .filter_map(|(lhs, rhs)| lhs.intersection(*rhs))
+            .collect::<HashSet<_>>();
+
+        let counted_twice = crossings
+            .into_iter()
+            .map(|it| self.segment_coverage(it).saturating_sub(1))
+            .sum::<usize>();
+
+        on_segments + lone_cells - counted_twice
     }
 
-    tail_seen.insert(knots[knots.len() - 1]);
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty() && self.segments.is_empty()
+    }
 }
 
-fn simulate_movement(dirs: &[Direction], num_knots: usize) -> HashSet<Pos> {
-    let mut tail_seen = HashSet::new();
-    tail_seen.insert(Pos { row: 0, col: 0 });
+// Follows the tail. Once the rope is straight, the rest of an instruction is applied in one go.
+#[must_use]
+#[allow(clippy::cast_possible_wrap)]
+pub fn simulate_movement(insts: &[Instruction], mut rope: Rope) -> Trail {
+    let mut trail = Trail::default();
+    trail.insert(rope.tail());
+
+    for inst in insts {
+        let mut remaining = inst.steps;
+
+        while remaining > 0 {
+            remaining -= 1;
 
-    let mut knots = vec![Pos { row: 0, col: 0 }; num_knots];
+            let straight = rope.step(inst.dir);
+            trail.insert(rope.tail());
 
-    for dir in dirs {
-        dir.evaluate(&mut knots);
-        update_tail_knots(&mut knots, &mut tail_seen);
+            if straight && remaining > 0 {
+                let from = rope.tail();
+                if rope.translate(inst.dir, remaining as i64) {
+                    trail.insert_segment(from, inst.dir, remaining as i64);
+                }
+
+                remaining = 0;
+            }
+        }
     }
 
-    tail_seen
+    trail
+}
+
+// Position of every knot after every single step
+#[derive(Clone, Debug)]
+pub struct RopeHistory {
+    instructions: Vec<Instruction>,
+    // `states[0]` is the initial state, `states[n]` the state after `n` steps
+    states: Vec<Vec<Pos>>,
 }
 
+impl RopeHistory {
+    #[must_use]
+    pub fn record(insts: &[Instruction], mut rope: Rope) -> Self {
+        let mut states = vec![rope.knots.clone()];
+
+        for inst in insts {
+            for _ in 0..inst.steps {
+                rope.step(inst.dir);
+                states.push(rope.knots.clone());
+            }
+        }
+
+        Self {
+            instructions: insts.to_vec(),
+            states,
+        }
+    }
+
+    #[must_use]
+    pub fn num_knots(&self) -> usize {
+        self.states[0].len()
+    }
+
+    #[must_use]
+    pub fn knot_history(&self, knot: usize) -> Vec<Pos> {
+        self.states.iter().map(|it| it[knot]).collect()
+    }
+
+    // (min row, max row, min col, max col) over everything that happened
+    fn bounds(&self) -> (i64, i64, i64, i64) {
+        let all = self.states.iter().flatten();
+        let (min_row, max_row) = all
+            .clone()
+            .map(|it| it.row)
+            .minmax()
+            .into_option()
+            .unwrap_or_default();
+        let (min_col, max_col) = all
+            .map(|it| it.col)
+            .minmax()
+            .into_option()
+            .unwrap_or_default();
+
+        (min_row, max_row, min_col, max_col)
+    }
+
+    fn render_grid<F: Fn(Pos) -> char>(&self, cell: F) -> String {
+        let (min_row, max_row, min_col, max_col) = self.bounds();
+
+        (min_row..=max_row)
+            .map(|row| {
+                (min_col..=max_col)
+                    .map(|col| cell(Pos { row, col }))
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+
+    fn knot_label(&self, knot: usize) -> char {
+        match knot {
+            0 => 'H',
+            _ if self.num_knots() == 2 => 'T',
+            _ => char::from_digit(knot as u32 % 10, 10).unwrap_or('?'),
+        }
+    }
+
+    // Same format as the puzzle, the knot listed first wins if several share a cell
+    #[must_use]
+    pub fn render_state(&self, step: usize) -> String {
+        let knots = &self.states[step];
+
+        self.render_grid(|pos| {
+            knots.iter().position(|it| *it == pos).map_or_else(
+                || if pos == Pos::default() { 's' } else { '.' },
+                |knot| self.knot_label(knot),
+            )
+        })
+    }
+
+    // The state after each instruction, headed by the instruction itself
+    #[must_use]
+    pub fn render_instructions(&self) -> String {
+        let mut step = 0;
+        let mut frames = vec![format!("== Initial State ==\n\n{}", self.render_state(0))];
+
+        for inst in &self.instructions {
+            step += inst.steps;
+            frames.push(format!("== {inst} ==\n\n{}", self.render_state(step)));
+        }
+
+        frames.join("\n\n")
+    }
+
+    // How many steps the knot spent on each cell
+    #[must_use]
+    pub fn heatmap(&self, knot: usize) -> HashMap<Pos, usize> {
+        self.states.iter().map(|it| it[knot]).counts()
+    }
+
+    // Visit counts as digits, '+' for ten or more visits
+    #[must_use]
+    pub fn render_heatmap(&self, knot: usize) -> String {
+        let heatmap = self.heatmap(knot);
+
+        self.render_grid(|pos| match heatmap.get(&pos) {
+            None => '.',
+            Some(&count) if count < 10 => char::from_digit(count as u32, 10).unwrap_or('+'),
+            Some(_) => '+',
+        })
+    }
+}
+
+#[must_use]
 #[aoc(day9, part1)]
-pub fn part1(dirs: &[Direction]) -> usize {
-    simulate_movement(dirs, 2).len()
+pub fn part1(insts: &[Instruction]) -> usize {
+    simulate_movement(insts, Rope::new(2)).len()
 }
 
+#[must_use]
 #[aoc(day9, part2)]
-pub fn part2(dirs: &[Direction]) -> usize {
-    simulate_movement(dirs, 10).len()
+pub fn part2(insts: &[Instruction]) -> usize {
+    simulate_movement(insts, Rope::new(10)).len()
 }
 
 #[cfg(test)]
@@ -136,7 +501,7 @@ mod tests {
 
     #[test]
     fn test_sample_p1() {
-        let data = generate(TEST_INPUT_P1);
+        let data = generate(TEST_INPUT_P1).expect("Failed generating test input");
         let res = part1(&data);
         assert_eq!(res, 13);
     }
@@ -152,14 +517,163 @@ mod tests {
 
     #[test]
     fn test_sample_p2() {
-        let data = generate(TEST_INPUT_P2);
+        let data = generate(TEST_INPUT_P2).expect("Failed generating test input");
         let res = part2(&data);
         assert_eq!(res, 36);
     }
 
     #[test]
     fn asd() {
-        let data = generate(TEST_INPUT_P1);
-        part2(&data);
+        let data = generate(TEST_INPUT_P1).expect("Failed generating test input");
+        assert_eq!(part2(&data), 1);
+    }
+
+    #[test]
+    fn test_render_instructions() {
+        let data = generate(TEST_INPUT_P1).expect("Failed generating test input");
+        let history = RopeHistory::record(&data, Rope::new(2));
+        let rendered = history.render_instructions();
+
+        let first = "== R 4 ==\n\n\
+                     ......\n\
+                     ......\n\
+                     ......\n\
+                     ......\n\
+                     s..TH.";
+        assert!(rendered.contains(first));
+
+        let last = "== R 2 ==\n\n\
+                    ......\n\
+                    ......\n\
+                    .TH...\n\
+                    ......\n\
+                    s.....";
+        assert!(rendered.ends_with(last));
+    }
+
+    #[test]
+    fn test_knot_histories() {
+        let data = generate(TEST_INPUT_P2).expect("Failed generating test input");
+        let history = RopeHistory::record(&data, Rope::new(10));
+
+        assert_eq!(
+            history.knot_history(0).len(),
+            1 + 5 + 8 + 8 + 3 + 17 + 10 + 25 + 20
+        );
+        assert_eq!(history.heatmap(9).len(), 36);
+        assert_eq!(history.heatmap(0).values().sum::<usize>(), 97);
+
+        // The tail covers the start
+        let state = "== U 8 ==\n\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ................H.........\n\
+                     ................1.........\n\
+                     ................2.........\n\
+                     ................3.........\n\
+                     ...............54.........\n\
+                     ..............6...........\n\
+                     .............7............\n\
+                     ............8.............\n\
+                     ...........9..............\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ..........................\n\
+                     ..........................";
+        assert!(history.render_instructions().contains(state));
+
+        let heatmap = history.render_heatmap(9);
+        assert_eq!(
+            heatmap
+                .chars()
+                .filter(|it| *it != '.' && *it != '\n')
+                .count(),
+            36
+        );
+    }
+
+    #[test]
+    fn test_diagonal_moves() {
+        let data = generate("UR 3\nDL 1\nDR 2").expect("Failed generating test input");
+        assert_eq!(data[0].dir, Direction::UpRight);
+        assert_eq!(data[2].dir, Direction::DownRight);
+
+        let history = RopeHistory::record(&data, Rope::new(2));
+        assert_eq!(
+            history.knot_history(1).last(),
+            Some(&Pos { row: -1, col: 3 })
+        );
+        assert_eq!(part1(&data), 4);
+    }
+
+    #[test]
+    fn test_slack_and_pinning() {
+        let data = generate("R 5").expect("Failed generating test input");
+
+        let loose = Rope::new(3).with_slack(0, 3);
+        let history = RopeHistory::record(&data, loose);
+        assert_eq!(
+            history.knot_history(1).last(),
+            Some(&Pos { row: 0, col: 2 })
+        );
+        assert_eq!(
+            history.knot_history(2).last(),
+            Some(&Pos { row: 0, col: 1 })
+        );
+
+        let tail_seen = simulate_movement(&data, Rope::new(3).with_uniform_slack(2));
+        assert_eq!(tail_seen.len(), 2);
+
+        let pinned = Rope::new(3).with_pinned(1);
+        let history = RopeHistory::record(&data, pinned);
+        assert_eq!(
+            history.knot_history(0).last(),
+            Some(&Pos { row: 0, col: 5 })
+        );
+        assert_eq!(history.knot_history(1).last(), Some(&Pos::default()));
+        assert_eq!(history.heatmap(2).len(), 1);
+
+        assert_eq!(
+            simulate_movement(&data, Rope::new(2).with_pinned(0)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_fast_forward_matches_steps() {
+        let inp = "R 40\nUL 25\nD 60\nL 7\nDR 30\nU 45\nL 80\nUR 12\nR 100\nDL 64\nU 3";
+        let data = generate(inp).expect("Failed generating test input");
+
+        for rope in [
+            Rope::new(2),
+            Rope::new(10),
+            Rope::new(6).with_uniform_slack(3),
+            Rope::new(8).with_pinned(5),
+        ] {
+            let trail = simulate_movement(&data, rope.clone());
+            let history = RopeHistory::record(&data, rope);
+            let tail = history.num_knots() - 1;
+
+            assert_eq!(trail.len(), history.heatmap(tail).len());
+            assert!(history
+                .knot_history(tail)
+                .iter()
+                .all(|it| trail.contains(*it)));
+        }
+    }
+
+    #[test]
+    fn test_huge_moves() {
+        let data = generate("R 10000000\nL 20000000").expect("Failed generating test input");
+        assert_eq!(part2(&data), 20_000_000 - 17);
+
+        assert!(generate("R 4\nX 1").is_none());
+        assert!(generate("R 99999999999999999999999").is_none());
     }
 }
@@ -1,7 +1,11 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
 use parse_display::{Display, FromStr};
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::RangeInclusive;
 
-#[derive(Display, FromStr, Copy, Clone)]
+#[derive(Display, FromStr, Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Instruction {
     #[display("noop")]
     Noop,
@@ -10,125 +14,671 @@ pub enum Instruction {
     Addx(i64),
 }
 
+impl Instruction {
+    #[must_use]
+    pub const fn cycles(self) -> usize {
+        match self {
+            Self::Noop => 1,
+            Self::Addx(_) => 2,
+        }
+    }
+}
+
+#[must_use]
 #[aoc_generator(day10)]
 pub fn generate(inp: &str) -> Vec<Instruction> {
     inp.lines()
         .filter_map(|it| it.parse::<Instruction>().ok())
-        .flat_map(|it| match it {
-            Instruction::Noop => vec![Instruction::Noop],
-            Instruction::Addx(n) => vec![Instruction::Noop, Instruction::Addx(n)],
-        })
         .collect()
 }
 
-struct CRTScreen {
-    pixels: [[char; 40]; 6],
-    row: usize,
-    col: usize,
+// What the CPU looks like *during* a cycle, i.e. before the instruction finishing in it takes effect
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CycleState {
+    pub cycle: i64,
+    pub register: i64,
+    pub instruction: Instruction,
+    // Index of the instruction being executed
+    pub pc: usize,
+}
+
+pub trait Observer {
+    fn on_cycle(&mut self, state: &CycleState);
+}
+
+impl<F: FnMut(&CycleState)> Observer for F {
+    fn on_cycle(&mut self, state: &CycleState) {
+        self(state);
+    }
+}
+
+// Sums `cycle * register` during the cycles `first`, `first + interval`, ...
+pub struct SignalStrength {
+    first: i64,
+    interval: i64,
+    total: i64,
+}
+
+impl SignalStrength {
+    // Samples at `first` and then every `interval` cycles, `None` for a zero interval
+    #[must_use]
+    pub const fn new(first: i64, interval: i64) -> Option<Self> {
+        if interval == 0 {
+            return None;
+        }
+
+        Some(Self {
+            first,
+            interval,
+            total: 0,
+        })
+    }
+
+    #[must_use]
+    pub const fn total(&self) -> i64 {
+        self.total
+    }
+}
+
+impl Observer for SignalStrength {
+    fn on_cycle(&mut self, state: &CycleState) {
+        let offset = state.cycle - self.first;
+        if offset >= 0 && offset % self.interval == 0 {
+            self.total += state.cycle * state.register;
+        }
+    }
+}
+
+pub const SCREEN_WIDTH: usize = 40;
+pub const SCREEN_HEIGHT: usize = 6;
+
+const GLYPH_WIDTH: usize = 4;
+const LETTER_WIDTH: usize = 5;
+
+const FONT: [(char, [&str; SCREEN_HEIGHT]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...", "#...", ".#.#", "..#.", "..#.", "..#."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+pub struct CRTScreen {
+    pixels: [[char; SCREEN_WIDTH]; SCREEN_HEIGHT],
+}
+
+impl Default for CRTScreen {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CRTScreen {
-    const fn new() -> Self {
+    #[must_use]
+    pub const fn new() -> Self {
         Self {
-            pixels: [['.'; 40]; 6],
-            row: 0,
-            col: 0,
+            pixels: [['.'; SCREEN_WIDTH]; SCREEN_HEIGHT],
         }
     }
 
-    #[allow(clippy::unused_self)]
+    // Reads the letters on screen, falls back to the raw pixels if some letter is unknown
     fn print_to_screen(&self) -> String {
-        /*
-        for row in self.pixels {
-            for col in row {
-                print!("{col}");
+        self.read_text().unwrap_or_else(|| self.render())
+    }
+
+    #[must_use]
+    pub fn from_image(image: &str) -> Option<Self> {
+        let mut screen = Self::new();
+
+        let rows = image.lines().collect::<Vec<_>>();
+        if rows.len() != SCREEN_HEIGHT {
+            return None;
+        }
+
+        for (row, line) in rows.iter().enumerate() {
+            let line = line.chars().collect::<Vec<_>>();
+            if line.len() != SCREEN_WIDTH || line.iter().any(|it| *it != '#' && *it != '.') {
+                return None;
             }
 
-            println!();
+            screen.pixels[row].copy_from_slice(&line);
         }
-        */
 
-        "BRJLFULP".to_string()
+        Some(screen)
     }
 
-    fn is_visible(&self, register: i64) -> bool {
-        for offset in -1i64..=1i64 {
-            #[allow(clippy::cast_possible_wrap)]
-            if register == self.col as i64 + offset {
-                return true;
+    // Writes up to 8 letters in the puzzle's font
+    #[must_use]
+    pub fn from_text(text: &str) -> Option<Self> {
+        let mut screen = Self::new();
+
+        for (idx, letter) in text.chars().enumerate() {
+            let (_, glyph) = FONT.iter().find(|(it, _)| *it == letter)?;
+            let start = idx * LETTER_WIDTH;
+            if start + GLYPH_WIDTH > SCREEN_WIDTH {
+                return None;
+            }
+
+            for (row, line) in glyph.iter().enumerate() {
+                for (col, pixel) in line.chars().enumerate() {
+                    screen.pixels[row][start + col] = pixel;
+                }
             }
         }
 
-        false
+        Some(screen)
     }
 
-    fn draw(&mut self, register: i64, cycle: i64) {
-        self.pixels[self.row][self.col] = if self.is_visible(register) { '#' } else { '.' };
+    #[must_use]
+    pub fn read_text(&self) -> Option<String> {
+        (0..SCREEN_WIDTH / LETTER_WIDTH)
+            .map(|idx| {
+                let start = idx * LETTER_WIDTH;
+                let glyph = self
+                    .pixels
+                    .iter()
+                    .map(|row| row[start..start + GLYPH_WIDTH].iter().collect::<String>())
+                    .collect::<Vec<_>>();
+
+                if glyph.iter().all(|row| !row.contains('#')) {
+                    return Some(None);
+                }
+
+                FONT.iter()
+                    .find(|(_, it)| *it == glyph.as_slice())
+                    .map(|(letter, _)| Some(*letter))
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|it| it.into_iter().flatten().collect())
+    }
+
+    #[must_use]
+    pub fn render(&self) -> String {
+        self.pixels
+            .iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 
-        self.row = (cycle / 40) as usize;
-        self.col = (self.col + 1) % 40;
+    // Row and column of the pixel drawn during `cycle`
+    #[must_use]
+    pub const fn pixel_for_cycle(cycle: i64) -> (usize, usize) {
+        let idx = (cycle - 1) as usize;
+        ((idx / SCREEN_WIDTH) % SCREEN_HEIGHT, idx % SCREEN_WIDTH)
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap)]
+    pub const fn is_visible(col: usize, register: i64) -> bool {
+        (register - col as i64).abs() <= 1
+    }
+}
+
+impl Observer for CRTScreen {
+    fn on_cycle(&mut self, state: &CycleState) {
+        let (row, col) = Self::pixel_for_cycle(state.cycle);
+        self.pixels[row][col] = if Self::is_visible(col, state.register) {
+            '#'
+        } else {
+            '.'
+        };
     }
 }
 
-struct VirtualMachine {
+pub struct VirtualMachine {
     instructions: Vec<Instruction>,
     register: i64,
     cycle: i64,
-    signal_strength: i64,
-    graphics: CRTScreen,
+    pc: usize,
+    // Cycles already spent on the current instruction
+    progress: usize,
 }
 
 impl VirtualMachine {
-    fn new(insts: &[Instruction]) -> Self {
+    #[must_use]
+    pub fn new(insts: &[Instruction]) -> Self {
         Self {
             instructions: insts.to_vec(),
             register: 1,
             cycle: 0,
-            signal_strength: 0,
-            graphics: CRTScreen::new(),
+            pc: 0,
+            progress: 0,
         }
     }
 
-    const fn collect_signal_strength(&self) -> bool {
-        (self.cycle - 20) % 40 == 0
+    #[must_use]
+    pub const fn register(&self) -> i64 {
+        self.register
+    }
+
+    #[must_use]
+    pub const fn cycle(&self) -> i64 {
+        self.cycle
     }
 
-    const fn total_signal_strength(&self) -> i64 {
-        self.signal_strength
+    // Index of the next instruction to execute
+    #[must_use]
+    pub const fn pc(&self) -> usize {
+        self.pc
     }
 
-    fn execute(&mut self) {
-        for inst in &self.instructions {
-            self.cycle += 1;
+    #[must_use]
+    pub const fn is_finished(&self) -> bool {
+        self.pc >= self.instructions.len()
+    }
 
-            if self.collect_signal_strength() {
-                self.signal_strength += self.register * self.cycle;
-            }
+    // Runs a single cycle, returns what the CPU looked like during it
+    pub fn step(&mut self) -> Option<CycleState> {
+        let instruction = *self.instructions.get(self.pc)?;
 
-            self.graphics.draw(self.register, self.cycle);
+        self.cycle += 1;
+        let state = CycleState {
+            cycle: self.cycle,
+            register: self.register,
+            instruction,
+            pc: self.pc,
+        };
 
-            match inst {
+        self.progress += 1;
+        if self.progress == instruction.cycles() {
+            match instruction {
                 Instruction::Noop => { /* do nothing */ }
                 Instruction::Addx(n) => {
                     self.register += n;
                 }
-            };
+            }
+
+            self.pc += 1;
+            self.progress = 0;
+        }
+
+        Some(state)
+    }
+
+    pub fn run(&mut self, observers: &mut [&mut dyn Observer]) {
+        while let Some(state) = self.step() {
+            for observer in observers.iter_mut() {
+                observer.on_cycle(&state);
+            }
         }
     }
 }
 
+// One source line of a program, with everything that happened while it was executed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListingLine {
+    pub pc: usize,
+    pub instruction: Instruction,
+    pub cycles: RangeInclusive<i64>,
+    // (row, col, pixel) for every pixel drawn during those cycles
+    pub pixels: Vec<(usize, usize, char)>,
+}
+
+impl fmt::Display for ListingLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cycles = format!("{}-{}", self.cycles.start(), self.cycles.end());
+        let drawn = self.pixels.iter().map(|it| it.2).collect::<String>();
+        let (row, col, _) = self.pixels[0];
+
+        write!(
+            f,
+            "{:>4}  {:<10} cycles {cycles:<8} pixels {row}:{col} {drawn}",
+            self.pc,
+            self.instruction.to_string()
+        )
+    }
+}
+
+#[must_use]
+pub fn disassemble(insts: &[Instruction]) -> Vec<ListingLine> {
+    let mut listing: Vec<ListingLine> = vec![];
+
+    let mut collect = |state: &CycleState| {
+        let (row, col) = CRTScreen::pixel_for_cycle(state.cycle);
+        let pixel = if CRTScreen::is_visible(col, state.register) {
+            '#'
+        } else {
+            '.'
+        };
+
+        match listing.last_mut() {
+            Some(line) if line.pc == state.pc => {
+                line.cycles = *line.cycles.start()..=state.cycle;
+                line.pixels.push((row, col, pixel));
+            }
+            _ => listing.push(ListingLine {
+                pc: state.pc,
+                instruction: state.instruction,
+                cycles: state.cycle..=state.cycle,
+                pixels: vec![(row, col, pixel)],
+            }),
+        }
+    };
+
+    VirtualMachine::new(insts).run(&mut [&mut collect]);
+    listing
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    // Stop before this cycle runs
+    Cycle(i64),
+    // Stop before the cycle drawing this (row, col) pixel
+    Pixel(usize, usize),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Watch {
+    // Log every change of the register
+    Changes,
+    // Stop once the register holds this value
+    Equals(i64),
+}
+
+// Runs a program cycle by cycle, driven by commands:
+//
+//   break cycle <n> | break pixel <row> <col> | clear
+//   watch | watch <value>
+//   step [<n>] | continue
+//   print | screen | list
+//
+// Empty lines and lines starting with '#' are ignored.
+pub struct Debugger {
+    program: Vec<Instruction>,
+    vm: VirtualMachine,
+    screen: CRTScreen,
+    breakpoints: HashSet<Breakpoint>,
+    watches: Vec<Watch>,
+    // Cycle whose breakpoint was hit last, so continuing doesn't stop there again
+    stopped_before: Option<i64>,
+}
+
+impl Debugger {
+    #[must_use]
+    pub fn new(insts: &[Instruction]) -> Self {
+        Self {
+            program: insts.to_vec(),
+            vm: VirtualMachine::new(insts),
+            screen: CRTScreen::new(),
+            breakpoints: HashSet::new(),
+            watches: vec![],
+            stopped_before: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    pub fn add_watch(&mut self, watch: Watch) {
+        self.watches.push(watch);
+    }
+
+    #[must_use]
+    pub const fn screen(&self) -> &CRTScreen {
+        &self.screen
+    }
+
+    fn breakpoint_before(&self, cycle: i64) -> Option<Breakpoint> {
+        let (row, col) = CRTScreen::pixel_for_cycle(cycle);
+
+        [Breakpoint::Cycle(cycle), Breakpoint::Pixel(row, col)]
+            .into_iter()
+            .find(|it| self.breakpoints.contains(it))
+    }
+
+    fn describe(&self) -> String {
+        let next = self.program.get(self.vm.pc()).map_or_else(
+            || "finished".to_string(),
+            |it| format!("next {it} (instruction {})", self.vm.pc()),
+        );
+
+        format!(
+            "cycle {} register {}: {next}",
+            self.vm.cycle(),
+            self.vm.register()
+        )
+    }
+
+    // Runs one cycle, returns false if the program already finished.
+    // Watch output goes to `out`, `stop` is set if an `Equals` watch triggered.
+    fn step_cycle(&mut self, out: &mut Vec<String>, stop: &mut bool) -> bool {
+        let before = self.vm.register();
+        let Some(state) = self.vm.step() else {
+            return false;
+        };
+        self.screen.on_cycle(&state);
+
+        let after = self.vm.register();
+        for watch in &self.watches {
+            match watch {
+                Watch::Changes if before != after => out.push(format!(
+                    "watch: register {before} -> {after} after cycle {} ({})",
+                    state.cycle, state.instruction
+                )),
+                Watch::Equals(value) if before != after && after == *value => {
+                    out.push(format!(
+                        "watch: register is {value} after cycle {}",
+                        state.cycle
+                    ));
+                    *stop = true;
+                }
+                _ => {}
+            }
+        }
+
+        true
+    }
+
+    // Stops if there is a breakpoint on the next cycle
+    fn hit_breakpoint(&mut self, out: &mut Vec<String>) -> bool {
+        let next = self.vm.cycle() + 1;
+        if self.vm.is_finished() || self.stopped_before == Some(next) {
+            return false;
+        }
+
+        let Some(breakpoint) = self.breakpoint_before(next) else {
+            return false;
+        };
+
+        out.push(format!("hit {breakpoint:?}: {}", self.describe()));
+        self.stopped_before = Some(next);
+        true
+    }
+
+    fn continue_running(&mut self, out: &mut Vec<String>) {
+        let mut stop = false;
+
+        if self.hit_breakpoint(out) {
+            return;
+        }
+
+        while self.step_cycle(out, &mut stop) {
+            if stop || self.hit_breakpoint(out) {
+                return;
+            }
+        }
+
+        out.push(format!("program finished after cycle {}", self.vm.cycle()));
+    }
+
+    // Executes a single command and appends its output to `out`
+    #[allow(clippy::missing_errors_doc)]
+    pub fn execute(&mut self, command: &str, out: &mut Vec<String>) -> Result<(), String> {
+        let parse_num = |it: Option<&str>| -> Result<i64, String> {
+            let it = it.ok_or("missing argument")?;
+            it.parse().map_err(|_| format!("invalid number '{it}'"))
+        };
+
+        let mut words = command.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("break"), Some("cycle")) => {
+                let cycle = parse_num(words.next())?;
+                if cycle < 1 {
+                    return Err(format!("cycle {cycle} is never run"));
+                }
+                self.add_breakpoint(Breakpoint::Cycle(cycle));
+            }
+            (Some("break"), Some("pixel")) => {
+                let (row, col) = (parse_num(words.next())?, parse_num(words.next())?);
+                let in_range =
+                    |value: i64, len: usize| usize::try_from(value).ok().filter(|it| *it < len);
+                match (in_range(row, SCREEN_HEIGHT), in_range(col, SCREEN_WIDTH)) {
+                    (Some(row), Some(col)) => self.add_breakpoint(Breakpoint::Pixel(row, col)),
+                    _ => return Err(format!("pixel ({row}, {col}) is off the screen")),
+                }
+            }
+            (Some("clear"), None) => self.breakpoints.clear(),
+            (Some("watch"), None) => self.add_watch(Watch::Changes),
+            (Some("watch"), value) => self.add_watch(Watch::Equals(parse_num(value)?)),
+            (Some("step"), count) => {
+                let count = count.map_or(Ok(1), |it| parse_num(Some(it)))?;
+                let mut stop = false;
+                for _ in 0..count {
+                    if !self.step_cycle(out, &mut stop) {
+                        break;
+                    }
+                }
+                out.push(self.describe());
+            }
+            (Some("continue"), None) => self.continue_running(out),
+            (Some("print"), None) => out.push(self.describe()),
+            (Some("screen"), None) => out.push(self.screen.render()),
+            (Some("list"), None) => {
+                out.extend(disassemble(&self.program).iter().map(ToString::to_string));
+            }
+            _ => return Err(format!("unknown command '{command}'")),
+        }
+
+        Ok(())
+    }
+
+    // Runs a whole command file, returning everything the commands printed
+    #[allow(clippy::missing_errors_doc)]
+    pub fn run_script(&mut self, script: &str) -> Result<Vec<String>, String> {
+        let mut out = vec![];
+
+        for (line_no, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            self.execute(line, &mut out)
+                .map_err(|err| format!("line {}: {err}", line_no + 1))?;
+        }
+
+        Ok(out)
+    }
+}
+
+// Register values that can be held during a cycle. Anything below -1 is dark everywhere.
+const PARKED: i64 = -2;
+
+// Generates a program drawing exactly `screen`.
+//
+// The register can only change at the end of an `addx`, i.e. after holding its value for
+// two cycles, and it starts at 1. So the first two pixels must always be lit, otherwise there
+// is no such program.
+#[must_use]
+#[allow(clippy::cast_possible_wrap)]
+pub fn assemble(screen: &CRTScreen) -> Option<Vec<Instruction>> {
+    const NUM_CYCLES: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+
+    let values = (PARKED..=SCREEN_WIDTH as i64).collect::<Vec<_>>();
+    let fits = |cycle: usize, value: i64| {
+        let (row, col) = CRTScreen::pixel_for_cycle(cycle as i64 + 1);
+        let lit = CRTScreen::is_visible(col, value);
+        lit == (screen.pixels[row][col] == '#')
+    };
+
+    // reachable[cycle][value][held]: the register can hold `value` during `cycle`, `held` if it
+    // has done so for at least two cycles
+    let mut reachable = vec![vec![[false; 2]; values.len()]; NUM_CYCLES];
+    let start = values.iter().position(|it| *it == 1)?;
+    reachable[0][start][0] = fits(0, 1);
+
+    for cycle in 1..NUM_CYCLES {
+        for (idx, &value) in values.iter().enumerate() {
+            if !fits(cycle, value) {
+                continue;
+            }
+
+            let prev = reachable[cycle - 1][idx];
+            reachable[cycle][idx][1] = prev[0] || prev[1];
+            reachable[cycle][idx][0] =
+                (0..values.len()).any(|it| it != idx && reachable[cycle - 1][it][1]);
+        }
+    }
+
+    // Walk backwards to find the value held during every cycle
+    let (mut idx, mut held) = (0..values.len())
+        .flat_map(|idx| [(idx, 1), (idx, 0)])
+        .find(|&(idx, held)| reachable[NUM_CYCLES - 1][idx][held])?;
+
+    let mut held_values = vec![values[idx]; NUM_CYCLES];
+    for cycle in (1..NUM_CYCLES).rev() {
+        held_values[cycle] = values[idx];
+
+        if held == 1 {
+            held = usize::from(reachable[cycle - 1][idx][1]);
+        } else {
+            idx = (0..values.len()).find(|&it| it != idx && reachable[cycle - 1][it][1])?;
+            held = 1;
+        }
+    }
+    held_values[0] = values[idx];
+
+    // Every run of the same value is some noops, ended by an addx if the value changes afterwards
+    let mut program = vec![];
+    for (value, run) in &held_values.iter().chunk_by(|it| **it) {
+        let len = run.count();
+        program.push((value, len));
+    }
+
+    let mut result = vec![];
+    for (idx, &(value, len)) in program.iter().enumerate() {
+        match program.get(idx + 1) {
+            Some(&(next, _)) => {
+                result.extend(std::iter::repeat_n(Instruction::Noop, len - 2));
+                result.push(Instruction::Addx(next - value));
+            }
+            None => result.extend(std::iter::repeat_n(Instruction::Noop, len)),
+        }
+    }
+
+    Some(result)
+}
+
+#[must_use]
+#[allow(clippy::missing_panics_doc)]
 #[aoc(day10, part1)]
 pub fn part1(insts: &[Instruction]) -> i64 {
-    let mut vm = VirtualMachine::new(insts);
-    vm.execute();
-    vm.total_signal_strength()
+    let mut signal = SignalStrength::new(20, 40).expect("Interval isn't zero");
+    VirtualMachine::new(insts).run(&mut [&mut signal]);
+    signal.total()
 }
 
+#[must_use]
 #[aoc(day10, part2)]
 pub fn part2(insts: &[Instruction]) -> String {
-    let mut vm = VirtualMachine::new(insts);
-    vm.execute();
-    vm.graphics.print_to_screen()
+    let mut screen = CRTScreen::new();
+    VirtualMachine::new(insts).run(&mut [&mut screen]);
+    screen.print_to_screen()
 }
 
 #[cfg(test)]
@@ -143,4 +693,198 @@ mod tests {
         let res = part1(&data);
         assert_eq!(13140, res);
     }
+
+    const TEST_SCREEN: &str = "##..##..##..##..##..##..##..##..##..##..\n\
+                               ###...###...###...###...###...###...###.\n\
+                               ####....####....####....####....####....\n\
+                               #####.....#####.....#####.....#####.....\n\
+                               ######......######......######......####\n\
+                               #######.......#######.......#######.....";
+
+    #[test]
+    fn test_observers_share_a_run() {
+        let data = generate(include_str!("../input/2022/day10_test.txt"));
+
+        let mut signal = SignalStrength::new(20, 40).expect("Interval isn't zero");
+        let mut screen = CRTScreen::new();
+        let mut cycles = vec![];
+        let mut trace = |state: &CycleState| cycles.push(*state);
+
+        VirtualMachine::new(&data).run(&mut [&mut signal, &mut screen, &mut trace]);
+
+        assert_eq!(signal.total(), 13140);
+        assert_eq!(screen.render(), TEST_SCREEN);
+        assert!(SignalStrength::new(20, 0).is_none());
+
+        assert_eq!(cycles.len(), 240);
+        assert_eq!(cycles[19].register, 21);
+        assert_eq!(cycles[19].instruction, Instruction::Addx(-1));
+    }
+
+    #[test]
+    fn test_step() {
+        let data = generate("noop\naddx 3\naddx -5");
+        let mut vm = VirtualMachine::new(&data);
+
+        let registers = std::iter::from_fn(|| vm.step())
+            .map(|it| (it.cycle, it.register, it.pc))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            registers,
+            vec![(1, 1, 0), (2, 1, 1), (3, 1, 1), (4, 4, 2), (5, 4, 2)]
+        );
+
+        assert!(vm.is_finished());
+        assert_eq!(vm.register(), -1);
+        assert_eq!(vm.cycle(), 5);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let data = generate("noop\naddx 3\naddx -5");
+        let listing = disassemble(&data);
+
+        assert_eq!(listing.len(), 3);
+        assert_eq!(listing[1].cycles, 2..=3);
+        assert_eq!(listing[1].pixels, vec![(0, 1, '#'), (0, 2, '#')]);
+        assert_eq!(listing[2].pixels, vec![(0, 3, '#'), (0, 4, '#')]);
+        assert_eq!(
+            listing[2].to_string(),
+            "   2  addx -5    cycles 4-5      pixels 0:3 ##"
+        );
+    }
+
+    #[test]
+    fn test_debugger_script() {
+        let data = generate(include_str!("../input/2022/day10_test.txt"));
+        let mut debugger = Debugger::new(&data);
+
+        let script = "# stop where the signal is sampled\n\
+                      break cycle 20\n\
+                      break pixel 1 0\n\
+                      continue\n\
+                      continue\n\
+                      step 2\n\
+                      watch 21\n\
+                      clear\n\
+                      continue\n\
+                      print";
+
+        let out = debugger.run_script(script).expect("Script should run");
+        assert_eq!(
+            out,
+            vec![
+                "hit Cycle(20): cycle 19 register 21: next addx -1 (instruction 10)",
+                "hit Pixel(1, 0): cycle 40 register 1: next addx 1 (instruction 20)",
+                "cycle 42 register 2: next addx 24 (instruction 21)",
+                "watch: register is 21 after cycle 139",
+                "cycle 139 register 21: next addx 1 (instruction 85)",
+            ]
+        );
+        assert_eq!(&debugger.screen().render()[..4], "##..");
+
+        let err = Debugger::new(&data).run_script("step\njump 3").err();
+        assert_eq!(err, Some("line 2: unknown command 'jump 3'".to_string()));
+
+        for (command, message) in [
+            ("break cycle -5", "cycle -5 is never run"),
+            ("break cycle 0", "cycle 0 is never run"),
+            ("break pixel -1 0", "pixel (-1, 0) is off the screen"),
+            ("break pixel 6 0", "pixel (6, 0) is off the screen"),
+            ("break pixel 0 40", "pixel (0, 40) is off the screen"),
+        ] {
+            let err = Debugger::new(&data).execute(command, &mut vec![]).err();
+            assert_eq!(err.as_deref(), Some(message));
+        }
+    }
+
+    #[test]
+    fn test_debugger_first_cycle() {
+        let data = generate("noop\naddx 3\naddx -5");
+
+        let out = Debugger::new(&data)
+            .run_script("break cycle 1\ncontinue\ncontinue")
+            .expect("Script should run");
+        assert_eq!(
+            out,
+            vec![
+                "hit Cycle(1): cycle 0 register 1: next noop (instruction 0)",
+                "program finished after cycle 5",
+            ]
+        );
+
+        let out = Debugger::new(&data)
+            .run_script("break pixel 0 0\nbreak cycle 2\ncontinue\ncontinue\ncontinue")
+            .expect("Script should run");
+        assert_eq!(
+            out,
+            vec![
+                "hit Pixel(0, 0): cycle 0 register 1: next noop (instruction 0)",
+                "hit Cycle(2): cycle 1 register 1: next addx 3 (instruction 1)",
+                "program finished after cycle 5",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debugger_watch_changes() {
+        let data = generate("noop\naddx 3\naddx -5");
+        let mut debugger = Debugger::new(&data);
+
+        let out = debugger
+            .run_script("watch\ncontinue")
+            .expect("Script should run");
+        assert_eq!(
+            out,
+            vec![
+                "watch: register 1 -> 4 after cycle 3 (addx 3)",
+                "watch: register 4 -> -1 after cycle 5 (addx -5)",
+                "program finished after cycle 5",
+            ]
+        );
+    }
+
+    fn run_program(program: &[Instruction]) -> CRTScreen {
+        let mut screen = CRTScreen::new();
+        VirtualMachine::new(program).run(&mut [&mut screen]);
+        screen
+    }
+
+    #[test]
+    fn test_read_text() {
+        let data = generate(include_str!("../input/2022/day10.txt"));
+        assert_eq!(part2(&data), "BRJLFULP");
+
+        let screen = CRTScreen::from_text("HI").expect("Known letters");
+        assert_eq!(screen.read_text(), Some("HI".to_string()));
+        assert!(CRTScreen::from_text("HELLO WORLD").is_none());
+    }
+
+    #[test]
+    fn test_assemble_round_trip() {
+        for text in ["BRJLFULP", "EFHKLPRZ", "BUBBLE"] {
+            let target = CRTScreen::from_text(text).expect("Known letters");
+            let program = assemble(&target).expect("Drawable image");
+
+            // Survives printing and parsing the program as well
+            let source = program.iter().join("\n");
+            let screen = run_program(&generate(&source));
+
+            assert_eq!(screen.render(), target.render());
+            assert_eq!(screen.read_text(), Some(text.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_assemble_images() {
+        let screen = CRTScreen::from_image(TEST_SCREEN).expect("Valid image");
+        let program = assemble(&screen).expect("Drawable image");
+        assert_eq!(run_program(&program).render(), TEST_SCREEN);
+
+        // The register starts at 1, so the first pixel is always lit
+        let dark = CRTScreen::from_image(&TEST_SCREEN.replacen('#', ".", 1)).expect("Valid image");
+        assert!(assemble(&dark).is_none());
+
+        assert!(CRTScreen::from_image("##..").is_none());
+    }
 }
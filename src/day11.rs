@@ -1,11 +1,22 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use num_bigint::BigUint;
+use std::collections::HashMap;
+use std::hash::Hash;
 
-#[derive(Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Add(u64),
+    Multiply(u64),
+    Square,
+}
+
+#[derive(Clone, Debug)]
 pub struct Monkey {
-    items: Vec<usize>,
-    operation: fn(usize) -> usize,
-    test: fn(usize) -> bool,
+    items: Vec<u64>,
+    operation: Operation,
+    // Items are thrown to `if_true` if their worry level is divisible by this
+    divisor: u64,
     if_true: usize,
     if_false: usize,
 }
@@ -14,114 +25,440 @@ fn get_monkeys() -> Vec<Monkey> {
     vec![
         Monkey {
             items: vec![64, 89, 65, 95],
-            operation: |it| it * 7,
-            test: |it| it % 3 == 0,
+            operation: Operation::Multiply(7),
+            divisor: 3,
             if_true: 4,
             if_false: 1,
         },
         Monkey {
             items: vec![76, 66, 74, 87, 70, 56, 51, 66],
-            operation: |it| it + 5,
-            test: |it| it % 13 == 0,
+            operation: Operation::Add(5),
+            divisor: 13,
             if_true: 7,
             if_false: 3,
         },
         Monkey {
             items: vec![91, 60, 63],
-            operation: |it| it * it,
-            test: |it| it % 2 == 0,
+            operation: Operation::Square,
+            divisor: 2,
             if_true: 6,
             if_false: 5,
         },
         Monkey {
             items: vec![92, 61, 79, 97, 79],
-            operation: |it| it + 6,
-            test: |it| it % 11 == 0,
+            operation: Operation::Add(6),
+            divisor: 11,
             if_true: 2,
             if_false: 6,
         },
         Monkey {
             items: vec![93, 54],
-            operation: |it| it * 11,
-            test: |it| it % 5 == 0,
+            operation: Operation::Multiply(11),
+            divisor: 5,
             if_true: 1,
             if_false: 7,
         },
         Monkey {
             items: vec![60, 79, 92, 69, 88, 82, 70],
-            operation: |it| it + 8,
-            test: |it| it % 17 == 0,
+            operation: Operation::Add(8),
+            divisor: 17,
             if_true: 4,
             if_false: 0,
         },
         Monkey {
             items: vec![64, 57, 73, 89, 55, 53],
-            operation: |it| it + 1,
-            test: |it| it % 19 == 0,
+            operation: Operation::Add(1),
+            divisor: 19,
             if_true: 0,
             if_false: 5,
         },
         Monkey {
             items: vec![62],
-            operation: |it| it + 4,
-            test: |it| it % 7 == 0,
+            operation: Operation::Add(4),
+            divisor: 7,
             if_true: 3,
             if_false: 2,
         },
     ]
 }
 
+#[must_use]
 #[aoc_generator(day11)]
 pub fn generate(_: &str) -> Vec<Monkey> {
     get_monkeys()
 }
 
-fn run_for_n_steps<F>(n: usize, monkeys: &[Monkey], relief: F) -> usize
+// How worry levels are tracked, each representation picks the relief it supports
+pub trait Worry: Clone {
+    type Relief: Copy;
+
+    #[must_use]
+    fn apply(&self, op: Operation) -> Self;
+    #[must_use]
+    fn relieve(&self, relief: Self::Relief) -> Self;
+    // `None` if the representation can't tell for this divisor
+    fn is_divisible_by(&self, divisor: u64) -> Option<bool>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Relief {
+    None,
+    DivideBy(u64),
+}
+
+impl Worry for BigUint {
+    type Relief = Relief;
+
+    fn apply(&self, op: Operation) -> Self {
+        match op {
+            Operation::Add(n) => self + n,
+            Operation::Multiply(n) => self * n,
+            Operation::Square => self * self,
+        }
+    }
+
+    fn relieve(&self, relief: Relief) -> Self {
+        match relief {
+            Relief::None => self.clone(),
+            Relief::DivideBy(n) => self / n,
+        }
+    }
+
+    fn is_divisible_by(&self, divisor: u64) -> Option<bool> {
+        Some((self % divisor) == Self::default())
+    }
+}
+
+// The worry level modulo every divisor any monkey tests for. Works for divisors that
+// aren't coprime as well, but can't be divided.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Residues {
+    residues: Vec<(u64, u64)>,
+}
+
+impl Residues {
+    #[must_use]
+    pub fn new(value: u64, divisors: &[u64]) -> Self {
+        Self {
+            residues: divisors.iter().map(|&d| (d, value % d)).collect(),
+        }
+    }
+}
+
+// Dividing isn't possible on residues, so there is no relief to pass
+impl Worry for Residues {
+    type Relief = ();
+
+    fn apply(&self, op: Operation) -> Self {
+        let residues = self
+            .residues
+            .iter()
+            .map(|&(d, r)| {
+                let (r, d128) = (u128::from(r), u128::from(d));
+                let next = match op {
+                    Operation::Add(n) => r + u128::from(n),
+                    Operation::Multiply(n) => r * (u128::from(n) % d128),
+                    Operation::Square => r * r,
+                };
+                (d, (next % d128) as u64)
+            })
+            .collect();
+
+        Self { residues }
+    }
+
+    fn relieve(&self, (): ()) -> Self {
+        self.clone()
+    }
+
+    // Divisors that weren't passed to `new` aren't tracked
+    fn is_divisible_by(&self, divisor: u64) -> Option<bool> {
+        self.residues
+            .iter()
+            .find(|(d, _)| *d == divisor)
+            .map(|(_, r)| *r == 0)
+    }
+}
+
+// Number of inspections per monkey, worry levels are created by `to_worry`. `None` if they
+// can't be tested against some monkey's divisor.
+pub fn count_inspections<W, F>(
+    monkeys: &[Monkey],
+    rounds: usize,
+    relief: W::Relief,
+    to_worry: F,
+) -> Option<Vec<u64>>
 where
-    F: Fn(usize) -> usize,
+    W: Worry,
+    F: Fn(u64) -> W,
 {
-    let mut monkeys = monkeys.to_vec();
     let num_monkeys = monkeys.len();
 
+    let mut items = monkeys
+        .iter()
+        .map(|it| it.items.iter().map(|&item| to_worry(item)).collect_vec())
+        .collect_vec();
     let mut inspects = vec![0; num_monkeys];
 
-    for _ in 0..n {
+    for _ in 0..rounds {
         for idx in 0..num_monkeys {
-            // modify items
-            monkeys[idx].items = monkeys[idx]
-                .items
-                .iter()
-                .map(|it| (monkeys[idx].operation)(*it))
-                .map(&relief)
-                .inspect(|_| inspects[idx] += 1)
-                .collect();
-
-            // throw items
-            let items = monkeys[idx].items.clone();
-            let true_case = monkeys[idx].if_true;
-            let false_case = monkeys[idx].if_false;
-            for it in items {
-                if (monkeys[idx].test)(it) {
-                    monkeys[true_case].items.push(it);
+            let monkey = &monkeys[idx];
+
+            for item in std::mem::take(&mut items[idx]) {
+                inspects[idx] += 1;
+
+                let item = item.apply(monkey.operation).relieve(relief);
+                let target = if item.is_divisible_by(monkey.divisor)? {
+                    monkey.if_true
                 } else {
-                    monkeys[false_case].items.push(it);
-                }
+                    monkey.if_false
+                };
+
+                items[target].push(item);
             }
+        }
+    }
+
+    Some(inspects)
+}
 
-            monkeys[idx].items.clear();
+// Uses residues if nothing needs to be divided, exact worry levels otherwise
+#[allow(clippy::missing_panics_doc)]
+pub fn inspections(monkeys: &[Monkey], rounds: usize, relief: Relief) -> Vec<u64> {
+    let inspects = match relief {
+        Relief::None => {
+            let divisors = monkeys.iter().map(|it| it.divisor).unique().collect_vec();
+            count_inspections(monkeys, rounds, (), |it| Residues::new(it, &divisors))
         }
+        Relief::DivideBy(_) => count_inspections(monkeys, rounds, relief, BigUint::from),
+    };
+
+    inspects.expect("Residues track every monkey's divisor")
+}
+
+// Moves a single item until it ends up with a monkey that already had its turn this round
+fn item_round<W: Worry>(
+    monkeys: &[Monkey],
+    (mut monkey, mut worry): (usize, W),
+    relief: W::Relief,
+    inspects: &mut [u64],
+) -> Option<(usize, W)> {
+    loop {
+        let current = &monkeys[monkey];
+        inspects[monkey] += 1;
+
+        worry = worry.apply(current.operation).relieve(relief);
+        let target = if worry.is_divisible_by(current.divisor)? {
+            current.if_true
+        } else {
+            current.if_false
+        };
+
+        if target <= monkey {
+            return Some((target, worry));
+        }
+
+        monkey = target;
     }
+}
+
+// Follows every item on its own until its (monkey, worry) state at the start of a round repeats,
+// then extrapolates the inspections of the remaining rounds
+pub fn fast_forward_inspections<W, F>(
+    monkeys: &[Monkey],
+    rounds: u64,
+    relief: W::Relief,
+    to_worry: F,
+) -> Option<Vec<u64>>
+where
+    W: Worry + Eq + Hash,
+    F: Fn(u64) -> W,
+{
+    let mut total = vec![0; monkeys.len()];
+
+    for (start, monkey) in monkeys.iter().enumerate() {
+        for &item in &monkey.items {
+            // `inspects[r]` holds the inspections during the first `r` rounds
+            let mut inspects = vec![vec![0u64; monkeys.len()]];
+            let mut seen: HashMap<(usize, W), usize> = HashMap::new();
+            let mut state = (start, to_worry(item));
+
+            let result = loop {
+                let round = inspects.len() - 1;
+                if round as u64 == rounds {
+                    break inspects[round].clone();
+                }
+
+                if let Some(&cycle_start) = seen.get(&state) {
+                    let cycle_len = (round - cycle_start) as u64;
+                    let remaining = rounds - cycle_start as u64;
+                    let (cycles, rest) = (remaining / cycle_len, (remaining % cycle_len) as usize);
+
+                    break (0..monkeys.len())
+                        .map(|idx| {
+                            let per_cycle = inspects[round][idx] - inspects[cycle_start][idx];
+                            inspects[cycle_start + rest][idx] + cycles * per_cycle
+                        })
+                        .collect();
+                }
+
+                seen.insert(state.clone(), round);
+
+                let mut next = inspects[round].clone();
+                state = item_round(monkeys, state, relief, &mut next)?;
+                inspects.push(next);
+            };
 
-    inspects.iter().sorted().rev().take(2).product()
+            for (sum, count) in total.iter_mut().zip(result) {
+                *sum += count;
+            }
+        }
+    }
+
+    Some(total)
 }
 
+// Same as `inspections`, but skips ahead once items start repeating
+#[allow(clippy::missing_panics_doc)]
+pub fn inspections_after(monkeys: &[Monkey], rounds: u64, relief: Relief) -> Vec<u64> {
+    let inspects = match relief {
+        Relief::None => {
+            let divisors = monkeys.iter().map(|it| it.divisor).unique().collect_vec();
+            fast_forward_inspections(monkeys, rounds, (), |it| Residues::new(it, &divisors))
+        }
+        Relief::DivideBy(_) => fast_forward_inspections(monkeys, rounds, relief, BigUint::from),
+    };
+
+    inspects.expect("Residues track every monkey's divisor")
+}
+
+// Product of the `top` highest inspection counts
+#[must_use]
+pub fn monkey_business(inspects: &[u64], top: usize) -> u128 {
+    inspects
+        .iter()
+        .sorted()
+        .rev()
+        .take(top)
+        .map(|&it| u128::from(it))
+        .product()
+}
+
+#[must_use]
 #[aoc(day11, part1)]
-pub fn part1(monkeys: &[Monkey]) -> usize {
-    run_for_n_steps(20, monkeys, |it| it / 3)
+pub fn part1(monkeys: &[Monkey]) -> u128 {
+    monkey_business(&inspections(monkeys, 20, Relief::DivideBy(3)), 2)
 }
 
+#[must_use]
 #[aoc(day11, part2)]
-pub fn part2(monkeys: &[Monkey]) -> usize {
-    let mod_by = 2 * 3 * 5 * 7 * 11 * 13 * 17 * 19;
-    run_for_n_steps(10_000, monkeys, |it| it % mod_by)
+pub fn part2(monkeys: &[Monkey]) -> u128 {
+    monkey_business(&inspections_after(monkeys, 10_000, Relief::None), 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_monkeys() -> Vec<Monkey> {
+        vec![
+            Monkey {
+                items: vec![79, 98],
+                operation: Operation::Multiply(19),
+                divisor: 23,
+                if_true: 2,
+                if_false: 3,
+            },
+            Monkey {
+                items: vec![54, 65, 75, 74],
+                operation: Operation::Add(6),
+                divisor: 19,
+                if_true: 2,
+                if_false: 0,
+            },
+            Monkey {
+                items: vec![79, 60, 97],
+                operation: Operation::Square,
+                divisor: 13,
+                if_true: 1,
+                if_false: 3,
+            },
+            Monkey {
+                items: vec![74],
+                operation: Operation::Add(3),
+                divisor: 17,
+                if_true: 0,
+                if_false: 1,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_sample_p1() {
+        let res = part1(&test_monkeys());
+        assert_eq!(res, 10605);
+    }
+
+    #[test]
+    fn test_sample_p2() {
+        let monkeys = test_monkeys();
+        assert_eq!(
+            inspections(&monkeys, 10_000, Relief::None),
+            vec![52166, 47830, 1938, 52013]
+        );
+        assert_eq!(part2(&monkeys), 2_713_310_158);
+    }
+
+    #[test]
+    fn test_residues_match_exact_worry() {
+        let mut monkeys = test_monkeys();
+        // Not coprime with the other divisors anymore
+        monkeys[3].divisor = 26;
+
+        for rounds in [1, 5, 15] {
+            let exact = count_inspections(&monkeys, rounds, Relief::None, BigUint::from);
+            assert_eq!(Some(inspections(&monkeys, rounds, Relief::None)), exact);
+        }
+
+        let exact = count_inspections(&get_monkeys(), 12, Relief::None, BigUint::from);
+        assert_eq!(Some(inspections(&get_monkeys(), 12, Relief::None)), exact);
+    }
+
+    #[test]
+    fn test_untracked_divisor() {
+        let residues = Residues::new(30, &[3, 5]);
+        assert_eq!(residues.is_divisible_by(5), Some(true));
+        assert_eq!(residues.is_divisible_by(7), None);
+
+        // Monkey 3 tests for 17, which isn't tracked
+        let monkeys = test_monkeys();
+        let to_worry = |it| Residues::new(it, &[23, 19, 13]);
+        assert_eq!(count_inspections(&monkeys, 1, (), to_worry), None);
+        assert_eq!(fast_forward_inspections(&monkeys, 1, (), to_worry), None);
+    }
+
+    #[test]
+    fn test_fast_forward_matches_simulation() {
+        for monkeys in [test_monkeys(), get_monkeys()] {
+            for (rounds, relief) in [(20, Relief::DivideBy(3)), (10_000, Relief::None)] {
+                let simulated = inspections(&monkeys, rounds, relief);
+                assert_eq!(
+                    inspections_after(&monkeys, rounds as u64, relief),
+                    simulated
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_huge_round_counts() {
+        let monkeys = test_monkeys();
+        let inspects = inspections_after(&monkeys, 1_000_000_000_000, Relief::None);
+
+        // Every item gets inspected at least once per round
+        let num_items = monkeys.iter().map(|it| it.items.len() as u64).sum::<u64>();
+        assert!(inspects.iter().sum::<u64>() >= num_items * 1_000_000_000_000);
+
+        assert_eq!(monkey_business(&inspects, 1), u128::from(inspects[0]));
+    }
 }
@@ -1,7 +1,11 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use pathfinding::matrix::Matrix;
+use pathfinding::prelude::dijkstra_all;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
 
+#[must_use]
 #[aoc_generator(day12)]
 pub fn generate(inp: &str) -> Option<Matrix<char>> {
     let rows = inp.lines().map(|it| it.chars().collect_vec()).collect_vec();
@@ -18,46 +22,175 @@ const fn char_to_elevation(c: char) -> u8 {
     }
 }
 
-fn successors((row, col): (usize, usize), grid: &Matrix<char>) -> Vec<((usize, usize), usize)> {
-    let current_elevation = char_to_elevation(grid[(row, col)]);
-    let is_one_step_up = |next| next <= current_elevation || next == current_elevation + 1;
+// Which steps are allowed and what they cost
+#[derive(Copy, Clone, Debug)]
+pub struct ClimbingRules {
+    pub max_ascent: u8,
+    pub max_descent: u8,
+    // Cost of a step, given the change in elevation (positive going up)
+    pub cost: fn(i32) -> usize,
+}
+
+impl Default for ClimbingRules {
+    // At most one step up, any step down, every step costs 1
+    fn default() -> Self {
+        Self {
+            max_ascent: 1,
+            max_descent: u8::MAX,
+            cost: |_| 1,
+        }
+    }
+}
+
+impl ClimbingRules {
+    fn step_cost(&self, from: char, to: char) -> Option<usize> {
+        let change = i32::from(char_to_elevation(to)) - i32::from(char_to_elevation(from));
+
+        let allowed = if change >= 0 {
+            change <= i32::from(self.max_ascent)
+        } else {
+            -change <= i32::from(self.max_descent)
+        };
+
+        allowed.then(|| (self.cost)(change))
+    }
+}
 
+// Cells from which `pos` can be reached in one step, i.e. the climbing rules flipped
+fn predecessors(
+    (row, col): (usize, usize),
+    grid: &Matrix<char>,
+    rules: &ClimbingRules,
+) -> Vec<((usize, usize), usize)> {
     grid.neighbours((row, col), false)
-        .fold(vec![], |mut acc, it| {
-            if is_one_step_up(char_to_elevation(grid[it])) {
-                acc.push((it, 1));
+        .filter_map(|it| Some((it, rules.step_cost(grid[it], grid[(row, col)])?)))
+        .collect()
+}
+
+// Distance from every cell to 'E', and the next cell on a shortest route from there
+pub struct DistanceField {
+    end: (usize, usize),
+    distances: Matrix<Option<usize>>,
+    next: HashMap<(usize, usize), (usize, usize)>,
+}
+
+impl DistanceField {
+    // Every step costs the same with the default rules, so a single breadth first search
+    // backwards from 'E' finds all distances
+    #[must_use]
+    pub fn new(grid: &Matrix<char>) -> Option<Self> {
+        let rules = ClimbingRules::default();
+        let end = grid.keys().find(|it| grid[*it] == 'E')?;
+
+        let mut distances = Matrix::new(grid.rows, grid.columns, None);
+        let mut next = HashMap::new();
+        let mut queue = VecDeque::from([(end, 0)]);
+        distances[end] = Some(0);
+
+        while let Some((pos, dist)) = queue.pop_front() {
+            for (prev, _) in predecessors(pos, grid, &rules) {
+                if distances[prev].is_none() {
+                    distances[prev] = Some(dist + 1);
+                    next.insert(prev, pos);
+                    queue.push_back((prev, dist + 1));
+                }
             }
+        }
+
+        Some(Self {
+            end,
+            distances,
+            next,
+        })
+    }
+
+    // Steps may cost different amounts here, so this needs Dijkstra
+    #[must_use]
+    pub fn with_rules(grid: &Matrix<char>, rules: &ClimbingRules) -> Option<Self> {
+        let end = grid.keys().find(|it| grid[*it] == 'E')?;
+        let reached = dijkstra_all(&end, |it| predecessors(*it, grid, rules));
+
+        let mut distances = Matrix::new(grid.rows, grid.columns, None);
+        distances[end] = Some(0);
+        for (pos, (_, cost)) in &reached {
+            distances[*pos] = Some(*cost);
+        }
+
+        let next = reached
+            .into_iter()
+            .map(|(pos, (parent, _))| (pos, parent))
+            .collect();
 
-            acc
+        Some(Self {
+            end,
+            distances,
+            next,
         })
+    }
+
+    #[must_use]
+    pub fn distance(&self, pos: (usize, usize)) -> Option<usize> {
+        self.distances[pos]
+    }
+
+    #[must_use]
+    pub const fn distances(&self) -> &Matrix<Option<usize>> {
+        &self.distances
+    }
+
+    // Every cell on a shortest route from `start` to 'E', including both
+    #[must_use]
+    pub fn route(&self, start: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        self.distance(start)?;
+
+        let mut route = vec![start];
+        while route[route.len() - 1] != self.end {
+            route.push(*self.next.get(&route[route.len() - 1])?);
+        }
+
+        Some(route)
+    }
 }
 
-fn find_shortest_path_from(start: (usize, usize), grid: &Matrix<char>) -> Option<usize> {
-    let (_, cost) = pathfinding::prelude::dijkstra(
-        &start,
-        |it| successors(*it, grid),
-        |pos| grid[*pos] == 'E',
-    )?;
+// Draws the route like the puzzle does, every cell points to the next one
+#[must_use]
+pub fn render_route(grid: &Matrix<char>, route: &[(usize, usize)]) -> String {
+    let mut canvas = Matrix::new(grid.rows, grid.columns, '.');
+
+    for (from, to) in route.iter().tuple_windows() {
+        canvas[*from] = match (to.0.cmp(&from.0), to.1.cmp(&from.1)) {
+            (Ordering::Less, _) => '^',
+            (Ordering::Greater, _) => 'v',
+            (_, Ordering::Less) => '<',
+            _ => '>',
+        };
+    }
 
-    Some(cost)
+    if let Some(last) = route.last() {
+        canvas[*last] = grid[*last];
+    }
+
+    canvas
+        .iter()
+        .map(|row| row.iter().collect::<String>())
+        .join("\n")
 }
 
+#[must_use]
 #[aoc(day12, part1)]
 pub fn part1(inp: &Matrix<char>) -> Option<usize> {
     let start_pos = inp.keys().find(|it| inp[*it] == 'S')?;
-    find_shortest_path_from(start_pos, inp)
+    DistanceField::new(inp)?.distance(start_pos)
 }
 
+#[must_use]
 #[aoc(day12, part2)]
 pub fn part2(inp: &Matrix<char>) -> Option<usize> {
+    let field = DistanceField::new(inp)?;
+
     inp.keys()
-        .filter_map(|it| {
-            if inp[it] == 'a' || inp[it] == 'S' {
-                find_shortest_path_from(it, inp)
-            } else {
-                None
-            }
-        })
+        .filter(|it| inp[*it] == 'a' || inp[*it] == 'S')
+        .filter_map(|it| field.distance(it))
         .min()
 }
 
@@ -84,4 +217,87 @@ mod tests {
         let res = part2(&data);
         assert_eq!(res, Some(29));
     }
+
+    #[test]
+    fn test_route() {
+        let data = generate(TEST_INPUT).expect("Failed generating test input");
+        let field = DistanceField::new(&data).expect("Grid has an end");
+
+        // The breadth first search agrees with Dijkstra on unit costs
+        let weighted =
+            DistanceField::with_rules(&data, &ClimbingRules::default()).expect("Grid has an end");
+        assert_eq!(field.distances(), weighted.distances());
+
+        let route = field.route((0, 0)).expect("End is reachable");
+        assert_eq!(route.len(), 32);
+        assert_eq!(route.last(), Some(&(2, 5)));
+        assert!(route
+            .iter()
+            .tuple_windows()
+            .all(
+                |(from, to)| from.0.abs_diff(to.0) + from.1.abs_diff(to.1) == 1
+                    && ClimbingRules::default()
+                        .step_cost(data[*from], data[*to])
+                        .is_some()
+            ));
+
+        let rendered = render_route(&data, &route);
+        assert_eq!(
+            rendered
+                .chars()
+                .filter(|it| *it != '.' && *it != '\n')
+                .count(),
+            32
+        );
+        assert!(rendered.starts_with('v') || rendered.starts_with('>'));
+    }
+
+    #[test]
+    fn test_render_route() {
+        let inp = "Sbcdefg\n\
+                   nmlkjih\n\
+                   opqrstu\n\
+                   aEzyxwv";
+        let data = generate(inp).expect("Failed generating test input");
+        let field = DistanceField::new(&data).expect("Grid has an end");
+        let route = field.route((0, 0)).expect("End is reachable");
+
+        let expected = ">>>>>>v\n\
+                        v<<<<<<\n\
+                        >>>>>>v\n\
+                        .E<<<<<";
+        assert_eq!(render_route(&data, &route), expected);
+        assert_eq!(field.distance((1, 0)), Some(13));
+        assert_eq!(field.distances()[(3, 1)], Some(0));
+        assert_eq!(field.distance((3, 0)), None);
+    }
+
+    #[test]
+    fn test_climbing_rules() {
+        let data = generate(TEST_INPUT).expect("Failed generating test input");
+
+        // Climbing costs extra, the route gets longer but not more expensive
+        let steep = ClimbingRules {
+            cost: |change| if change > 0 { 3 } else { 1 },
+            ..ClimbingRules::default()
+        };
+        let field = DistanceField::with_rules(&data, &steep).expect("Grid has an end");
+        assert_eq!(field.distance((0, 0)), Some(31 + 2 * 25));
+
+        // Can't go down more than one level at once either
+        let careful = ClimbingRules {
+            max_descent: 1,
+            ..ClimbingRules::default()
+        };
+        let field = DistanceField::with_rules(&data, &careful).expect("Grid has an end");
+        assert_eq!(field.distance((0, 0)), Some(31));
+        assert_eq!(field.distance((4, 0)), Some(29));
+
+        let flat = ClimbingRules {
+            max_ascent: 0,
+            ..ClimbingRules::default()
+        };
+        let field = DistanceField::with_rules(&data, &flat).expect("Grid has an end");
+        assert_eq!(field.distance((0, 0)), None);
+    }
 }
@@ -1,6 +1,10 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use serde_json::{Deserializer, Value};
 use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::io::Read;
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum Node {
@@ -8,65 +12,152 @@ pub enum Node {
     List(Vec<Self>),
 }
 
-fn parse_numbers_list(line: &str) -> Option<Node> {
-    if line.contains('[') || line.contains(']') {
-        return None;
+// Lists nest at most this deep, so comparing, printing and dropping packets can safely recurse
+pub const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
     }
+}
+
+impl Error for ParseError {}
 
-    Some(Node::List(
-        line.split(',')
-            .filter_map(|it| it.parse::<i64>().ok())
-            .map(Node::Integer)
-            .collect(),
-    ))
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
 }
 
-fn parse_node(line: &str) -> Option<Node> {
-    let line = line.strip_prefix('[')?.strip_suffix(']')?;
-    if let Some(numbers_list) = parse_numbers_list(line) {
-        return Some(numbers_list);
+// Parses an optionally negative integer starting at `pos`, returns it and the position after it
+fn parse_integer(bytes: &[u8], mut pos: usize) -> Result<(i64, usize), (usize, &'static str)> {
+    let start = pos;
+    let negative = bytes.get(pos) == Some(&b'-');
+    if negative {
+        pos += 1;
     }
 
-    let mut data = vec![];
+    if !bytes.get(pos).is_some_and(u8::is_ascii_digit) {
+        return Err((pos, "expected a digit"));
+    }
 
-    let mut chrs = line.chars().collect_vec();
-    while !chrs.is_empty() {
-        if chrs[0] == ',' {
-            chrs.drain(..1);
-            continue;
-        }
+    let mut value: i64 = 0;
+    while let Some(digit) = bytes.get(pos).filter(|it| it.is_ascii_digit()) {
+        let digit = i64::from(digit - b'0');
+        // Accumulate towards the sign so i64::MIN still fits
+        value = value
+            .checked_mul(10)
+            .and_then(|it| {
+                if negative {
+                    it.checked_sub(digit)
+                } else {
+                    it.checked_add(digit)
+                }
+            })
+            .ok_or((start, "integer out of range"))?;
+        pos += 1;
+    }
 
-        if chrs[0] == '[' {
-            let mut end_idx = 0;
-            let mut stack = 0;
-            for (idx, c) in chrs.iter().enumerate() {
-                if *c == '[' {
-                    stack += 1;
-                } else if *c == ']' {
-                    stack -= 1;
-                    if stack == 0 {
-                        end_idx = idx;
-                        break;
-                    }
+    Ok((value, pos))
+}
+
+// Single pass over the bytes, open lists live on an explicit stack so rejecting packets nested
+// deeper than `MAX_DEPTH` doesn't depend on the stack size
+fn parse_bytes(bytes: &[u8]) -> Result<Node, (usize, &'static str)> {
+    let mut stack: Vec<Vec<Node>> = vec![];
+    let mut pos = skip_whitespace(bytes, 0);
+    if bytes.get(pos).is_some_and(|it| *it != b'[') {
+        return Err((pos, "packets have to be lists"));
+    }
+
+    loop {
+        // Expecting a value here
+        let mut node = match bytes.get(pos) {
+            Some(b'[') => {
+                if stack.len() == MAX_DEPTH {
+                    return Err((pos, "lists nested too deeply"));
+                }
+                pos = skip_whitespace(bytes, pos + 1);
+                if bytes.get(pos) == Some(&b']') {
+                    pos += 1;
+                    Node::List(vec![])
+                } else {
+                    stack.push(vec![]);
+                    continue;
                 }
             }
-
-            let sub_line = chrs[..=end_idx].iter().join("");
-            data.push(parse_node(&sub_line)?);
-            chrs.drain(..=end_idx);
-        } else if chrs[0].is_ascii_digit() {
-            let digits = chrs.iter().take_while(|it| it.is_ascii_digit()).join("");
-            data.push(Node::Integer(digits.parse::<i64>().ok()?));
-            chrs.drain(..digits.len());
+            Some(b'-' | b'0'..=b'9') => {
+                let (value, next) = parse_integer(bytes, pos)?;
+                pos = next;
+                Node::Integer(value)
+            }
+            Some(_) => return Err((pos, "expected an integer or '['")),
+            None => return Err((pos, "unexpected end of input")),
+        };
+
+        // A value is complete, close as many lists as it ends
+        loop {
+            pos = skip_whitespace(bytes, pos);
+            let Some(list) = stack.last_mut() else {
+                return if pos == bytes.len() {
+                    Ok(node)
+                } else {
+                    Err((pos, "unexpected characters after the packet"))
+                };
+            };
+
+            list.push(node);
+            match bytes.get(pos) {
+                Some(b',') => {
+                    pos = skip_whitespace(bytes, pos + 1);
+                    break;
+                }
+                Some(b']') => {
+                    pos += 1;
+                    node = Node::List(stack.pop().unwrap_or_default());
+                }
+                Some(_) => return Err((pos, "expected ',' or ']'")),
+                None => return Err((pos, "unexpected end of input")),
+            }
         }
     }
+}
 
-    Some(Node::List(data))
+#[allow(clippy::missing_errors_doc)]
+pub fn parse_node(line: &str) -> Result<Node, ParseError> {
+    parse_bytes(line.as_bytes()).map_err(|(pos, message)| ParseError {
+        line: 1,
+        column: pos + 1,
+        message,
+    })
 }
 
+// Every non-empty line is a packet, the first malformed one fails the whole input
+#[allow(clippy::missing_errors_doc)]
 #[aoc_generator(day13)]
-pub fn generate(inp: &str) -> Vec<Node> {
-    inp.lines().filter_map(parse_node).collect()
+pub fn generate(inp: &str) -> Result<Vec<Node>, ParseError> {
+    inp.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            parse_node(line).map_err(|err| ParseError {
+                line: idx + 1,
+                ..err
+            })
+        })
+        .collect()
 }
 
 fn compare_lists(left: &[Node], right: &[Node]) -> Ordering {
@@ -93,19 +184,303 @@ fn compare_lists(left: &[Node], right: &[Node]) -> Ordering {
     left.len().cmp(&right.len())
 }
 
+// The puzzle's comparison, an integer compared against a list is promoted to a one element list
 fn compare_nodes(left: &Node, right: &Node) -> Ordering {
     match (left, right) {
         (Node::Integer(l), Node::Integer(r)) => l.cmp(r),
         (Node::List(ls), Node::List(rs)) => compare_lists(ls, rs),
-        (Node::Integer(l), Node::List(_)) => {
-            compare_nodes(&Node::List(vec![Node::Integer(*l)]), right)
+        (Node::Integer(_), Node::List(rs)) => compare_lists(std::slice::from_ref(left), rs),
+        (Node::List(ls), Node::Integer(_)) => compare_lists(ls, std::slice::from_ref(right)),
+    }
+}
+
+// Plain structural order, integers before lists
+fn compare_structure(left: &Node, right: &Node) -> Ordering {
+    match (left, right) {
+        (Node::Integer(l), Node::Integer(r)) => l.cmp(r),
+        (Node::List(ls), Node::List(rs)) => ls
+            .iter()
+            .zip(rs)
+            .map(|(l, r)| compare_structure(l, r))
+            .find(|it| it.is_ne())
+            .unwrap_or_else(|| ls.len().cmp(&rs.len())),
+        (Node::Integer(_), Node::List(_)) => Ordering::Less,
+        (Node::List(_), Node::Integer(_)) => Ordering::Greater,
+    }
+}
+
+// Follows the puzzle rules. Those consider `1`, `[1]` and `[[1]]` equal, so ties are broken by
+// structure to keep the order consistent with `Eq`
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_nodes(self, other).then_with(|| compare_structure(self, other))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Same format as the puzzle input, so it parses back into the same node
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::List(items) => write!(f, "[{}]", items.iter().join(",")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    Syntax(serde_json::Error),
+    // A JSON value with no packet equivalent, `path` points at it like `$[1][0]`
+    Unsupported { path: String, found: String },
+    InPacket { packet: usize, error: Box<Self> },
+    UnpairedPacket { packet: usize },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(err) => write!(f, "invalid JSON: {err}"),
+            Self::Unsupported { path, found } => {
+                write!(
+                    f,
+                    "{path}: packets only hold integers and lists, found {found}"
+                )
+            }
+            Self::InPacket { packet, error } => write!(f, "packet {packet}: {error}"),
+            Self::UnpairedPacket { packet } => write!(f, "packet {packet} has no pair"),
         }
-        (Node::List(_), Node::Integer(r)) => {
-            compare_nodes(left, &Node::List(vec![Node::Integer(*r)]))
+    }
+}
+
+impl Error for JsonError {}
+
+fn node_from_json(value: &Value, path: &mut String, depth: usize) -> Result<Node, JsonError> {
+    let unsupported = |path: &str, found: String| JsonError::Unsupported {
+        path: path.to_string(),
+        found,
+    };
+
+    match value {
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => Ok(Node::Integer(value)),
+            None if number.is_f64() => Err(unsupported(path, format!("float {number}"))),
+            None => Err(unsupported(path, format!("out of range integer {number}"))),
+        },
+        Value::Array(_) if depth == MAX_DEPTH => Err(unsupported(
+            path,
+            format!("lists nested deeper than {MAX_DEPTH}"),
+        )),
+        Value::Array(items) => {
+            let mut nodes = Vec::with_capacity(items.len());
+            for (idx, item) in items.iter().enumerate() {
+                let len = path.len();
+                let _ = write!(path, "[{idx}]");
+                nodes.push(node_from_json(item, path, depth + 1)?);
+                path.truncate(len);
+            }
+            Ok(Node::List(nodes))
         }
+        Value::String(text) => Err(unsupported(path, format!("string {text:?}"))),
+        Value::Object(_) => Err(unsupported(path, "object".to_string())),
+        Value::Bool(flag) => Err(unsupported(path, format!("boolean {flag}"))),
+        Value::Null => Err(unsupported(path, "null".to_string())),
+    }
+}
+
+impl TryFrom<&Value> for Node {
+    type Error = JsonError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        node_from_json(value, &mut "$".to_string(), 0)
     }
 }
 
+impl From<&Node> for Value {
+    fn from(node: &Node) -> Self {
+        match node {
+            Node::Integer(value) => Self::from(*value),
+            Node::List(items) => Self::Array(items.iter().map(Self::from).collect()),
+        }
+    }
+}
+
+// Reads a stream of JSON arrays (any whitespace in between) and pairs them up in order, so
+// puzzle input and pretty printed JSON both work. Packets are counted from 1 in errors.
+#[allow(clippy::missing_errors_doc)]
+pub fn read_json_pairs<R: Read>(reader: R) -> Result<Vec<(Node, Node)>, JsonError> {
+    let packets = Deserializer::from_reader(reader)
+        .into_iter::<Value>()
+        .enumerate()
+        .map(|(idx, value)| {
+            let value = value.map_err(JsonError::Syntax)?;
+            Node::try_from(&value).map_err(|error| JsonError::InPacket {
+                packet: idx + 1,
+                error: Box::new(error),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if packets.len() % 2 == 1 {
+        return Err(JsonError::UnpairedPacket {
+            packet: packets.len(),
+        });
+    }
+
+    Ok(packets.into_iter().tuples().collect())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Integers(i64, i64),
+    LeftRanOut,
+    RightRanOut,
+}
+
+// How the puzzle rules ordered a pair, with the same trace the puzzle description shows
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    pub order: Ordering,
+    pub decision: Option<Decision>,
+    // List indices leading to where the decision was made. The two differ once an integer
+    // has been promoted, as the promoted list isn't part of the packet.
+    pub left_path: Vec<usize>,
+    pub right_path: Vec<usize>,
+    pub trace: Vec<String>,
+}
+
+impl Explanation {
+    fn line(&mut self, depth: usize, text: &str) {
+        self.trace.push(format!("{}- {text}", "  ".repeat(depth)));
+    }
+
+    fn decide(&mut self, depth: usize, decision: Decision, order: Ordering) -> Ordering {
+        let reason = match decision {
+            Decision::Integers(..) if order.is_lt() => "Left side is smaller",
+            Decision::Integers(..) => "Right side is smaller",
+            Decision::LeftRanOut => "Left side ran out of items",
+            Decision::RightRanOut => "Right side ran out of items",
+        };
+        let verdict = if order.is_lt() { "" } else { "not " };
+        self.line(
+            depth,
+            &format!("{reason}, so inputs are {verdict}in the right order"),
+        );
+
+        self.decision = Some(decision);
+        order
+    }
+
+    fn compare(&mut self, left: &Node, right: &Node, depth: usize) -> Ordering {
+        self.line(depth, &format!("Compare {left} vs {right}"));
+
+        match (left, right) {
+            (Node::Integer(l), Node::Integer(r)) => match l.cmp(r) {
+                Ordering::Equal => Ordering::Equal,
+                order => self.decide(depth + 1, Decision::Integers(*l, *r), order),
+            },
+            (Node::List(ls), Node::List(rs)) => {
+                self.compare_lists(ls, rs, (false, false), depth + 1)
+            }
+            (Node::Integer(_), Node::List(rs)) => {
+                self.line(
+                    depth + 1,
+                    &format!("Mixed types; convert left to [{left}] and retry comparison"),
+                );
+                self.line(depth + 1, &format!("Compare [{left}] vs {right}"));
+                self.compare_lists(std::slice::from_ref(left), rs, (true, false), depth + 2)
+            }
+            (Node::List(ls), Node::Integer(_)) => {
+                self.line(
+                    depth + 1,
+                    &format!("Mixed types; convert right to [{right}] and retry comparison"),
+                );
+                self.line(depth + 1, &format!("Compare {left} vs [{right}]"));
+                self.compare_lists(ls, std::slice::from_ref(right), (false, true), depth + 2)
+            }
+        }
+    }
+
+    // Paths are only unwound when the lists are equal, so they end up at the decision
+    fn compare_lists(
+        &mut self,
+        left: &[Node],
+        right: &[Node],
+        (left_promoted, right_promoted): (bool, bool),
+        depth: usize,
+    ) -> Ordering {
+        for idx in 0.. {
+            let (l, r) = match (left.get(idx), right.get(idx)) {
+                (Some(l), Some(r)) => (l, r),
+                (None, Some(_)) => return self.decide(depth, Decision::LeftRanOut, Ordering::Less),
+                (Some(_), None) => {
+                    return self.decide(depth, Decision::RightRanOut, Ordering::Greater)
+                }
+                (None, None) => break,
+            };
+
+            if !left_promoted {
+                self.left_path.push(idx);
+            }
+            if !right_promoted {
+                self.right_path.push(idx);
+            }
+
+            let order = self.compare(l, r, depth);
+            if order.is_ne() {
+                return order;
+            }
+
+            if !left_promoted {
+                self.left_path.pop();
+            }
+            if !right_promoted {
+                self.right_path.pop();
+            }
+        }
+
+        Ordering::Equal
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.trace.join("\n"))
+    }
+}
+
+#[must_use]
+pub fn explain(left: &Node, right: &Node) -> Explanation {
+    let mut explanation = Explanation {
+        order: Ordering::Equal,
+        decision: None,
+        left_path: vec![],
+        right_path: vec![],
+        trace: vec![],
+    };
+
+    explanation.order = explanation.compare(left, right, 0);
+    explanation
+}
+
+// Traces for every pair of packets, formatted like the puzzle's walkthrough of the example
+#[must_use]
+pub fn explain_pairs(packets: &[Node]) -> String {
+    packets
+        .iter()
+        .tuples()
+        .enumerate()
+        .map(|(idx, (left, right))| format!("== Pair {} ==\n{}", idx + 1, explain(left, right)))
+        .join("\n\n")
+}
+
+#[must_use]
+#[allow(clippy::missing_panics_doc)]
 #[aoc(day13, part1)]
 pub fn part1(lists: &[Node]) -> usize {
     let even_entry = lists.iter().step_by(2).cloned().collect_vec();
@@ -125,35 +500,45 @@ pub fn part1(lists: &[Node]) -> usize {
     result
 }
 
-#[aoc(day13, part2)]
-pub fn part2(lists: &[Node]) -> usize {
-    let mut lists = lists.to_vec();
-    // [[2]]
-    let decoder_2 = Node::List(vec![Node::List(vec![Node::Integer(2)])]);
-    lists.push(decoder_2.clone());
+// 1-based position of every divider once it's sorted in with the packets, found by counting
+// the packets (and other dividers) ordering before it
+#[must_use]
+pub fn divider_positions(packets: &[Node], dividers: &[Node]) -> Vec<usize> {
+    dividers
+        .iter()
+        .map(|divider| {
+            let packets_before = packets.iter().filter(|it| *it < divider).count();
+            let dividers_before = dividers.iter().filter(|it| *it < divider).count();
+            packets_before + dividers_before + 1
+        })
+        .collect()
+}
 
-    // [[6]]
-    let decoder_6 = Node::List(vec![Node::List(vec![Node::Integer(6)])]);
-    lists.push(decoder_6.clone());
+// Packets and dividers in sorted order, for when the full order is needed
+#[must_use]
+pub fn sorted_with_dividers(packets: &[Node], dividers: &[Node]) -> Vec<Node> {
+    packets.iter().chain(dividers).cloned().sorted().collect()
+}
 
-    lists.sort_by(compare_nodes);
+#[must_use]
+pub fn decoder_dividers() -> [Node; 2] {
+    // [[2]] and [[6]]
+    [2, 6].map(|it| Node::List(vec![Node::List(vec![Node::Integer(it)])]))
+}
 
-    lists
-        .iter()
-        .enumerate()
-        .filter_map(|(idx, it)| {
-            if *it == decoder_2 || *it == decoder_6 {
-                Some(idx + 1)
-            } else {
-                None
-            }
-        })
+#[must_use]
+#[aoc(day13, part2)]
+pub fn part2(lists: &[Node]) -> usize {
+    divider_positions(lists, &decoder_dividers())
+        .into_iter()
         .product()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
 
     #[test]
     fn test_comparison() {
@@ -219,7 +604,7 @@ mod tests {
     #[test]
     fn test_sample_p1() {
         let inp = include_str!("../input/2022/day13_test.txt");
-        let data = generate(inp);
+        let data = generate(inp).expect("Failed parsing test input");
         let res = part1(&data);
         assert_eq!(res, 13);
     }
@@ -227,8 +612,339 @@ mod tests {
     #[test]
     fn test_sample_p2() {
         let inp = include_str!("../input/2022/day13_test.txt");
-        let data = generate(inp);
+        let data = generate(inp).expect("Failed parsing test input");
         let res = part2(&data);
         assert_eq!(res, 140);
     }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_node("[]"), Ok(Node::List(vec![])));
+        assert_eq!(
+            parse_node("[[1],[2,-3],[[]],-40]"),
+            Ok(Node::List(vec![
+                Node::List(vec![Node::Integer(1)]),
+                Node::List(vec![Node::Integer(2), Node::Integer(-3)]),
+                Node::List(vec![Node::List(vec![])]),
+                Node::Integer(-40),
+            ]))
+        );
+        assert_eq!(parse_node(" [ 1 , 2 ] "), parse_node("[1,2]"));
+        assert_eq!(
+            parse_node("[-9223372036854775808]"),
+            Ok(Node::List(vec![Node::Integer(i64::MIN)]))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |line: &str| parse_node(line).map_err(|it| (it.column, it.message));
+
+        assert_eq!(err("[1,2"), Err((5, "unexpected end of input")));
+        assert_eq!(err("[1,,2]"), Err((4, "expected an integer or '['")));
+        assert_eq!(err("[1 2]"), Err((4, "expected ',' or ']'")));
+        assert_eq!(
+            err("[1]]"),
+            Err((4, "unexpected characters after the packet"))
+        );
+        assert_eq!(err("[-]"), Err((3, "expected a digit")));
+        assert_eq!(
+            err("[9223372036854775808]"),
+            Err((2, "integer out of range"))
+        );
+        assert_eq!(err(""), Err((1, "unexpected end of input")));
+        assert_eq!(err("7"), Err((1, "packets have to be lists")));
+        assert_eq!(err("  -1"), Err((3, "packets have to be lists")));
+
+        let err = generate("[1]\n[2]\n\n[3,x]").expect_err("Last packet is malformed");
+        assert_eq!(
+            err.to_string(),
+            "line 4, column 4: expected an integer or '['"
+        );
+    }
+
+    #[test]
+    fn test_parse_deep_nesting() {
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+
+        let node = parse_node(&nested(MAX_DEPTH)).expect("Nesting up to the limit should parse");
+        let mut levels = 1;
+        let mut current = &node;
+        while let Node::List(items) = current {
+            let Some(inner) = items.first() else { break };
+            current = inner;
+            levels += 1;
+        }
+        assert_eq!(levels, MAX_DEPTH);
+
+        // Packets at the limit still compare, print and convert without overflowing
+        let other = "[".repeat(MAX_DEPTH) + "1" + &"]".repeat(MAX_DEPTH);
+        let other = parse_node(&other).expect("Valid packet");
+        assert_eq!(node.cmp(&other), Ordering::Less);
+        assert_eq!(part1(&[node.clone(), other]), 1);
+        assert_eq!(node.to_string(), nested(MAX_DEPTH));
+        let json = Value::from(&node);
+        assert_eq!(Node::try_from(&json).expect("Within the limit"), node);
+
+        let deeper = Value::Array(vec![json]);
+        let err = Node::try_from(&deeper).expect_err("Nested too deeply");
+        assert!(err.to_string().contains("nested deeper than 256"));
+
+        let err = parse_node(&nested(MAX_DEPTH + 1)).expect_err("Nested too deeply");
+        assert_eq!(err.column, MAX_DEPTH + 1);
+        assert_eq!(err.message, "lists nested too deeply");
+
+        let err = generate(&format!("[]\n{}", nested(200_000))).expect_err("Nested too deeply");
+        assert_eq!((err.line, err.column), (2, MAX_DEPTH + 1));
+
+        let unclosed = "[1,".repeat(200_000);
+        assert!(parse_node(&unclosed).is_err());
+    }
+
+    #[test]
+    fn test_ord() {
+        let node = |line: &str| parse_node(line).expect("Valid packet");
+
+        let mut sorted = [
+            "[[1],4]",
+            "[1,1,3,1,1]",
+            "[]",
+            "[[]]",
+            "[1,1,5,1,1]",
+            "[[1],[2,3,4]]",
+        ]
+        .map(node)
+        .to_vec();
+        sorted.sort();
+        assert_eq!(
+            sorted.iter().map(Node::to_string).collect_vec(),
+            vec![
+                "[]",
+                "[[]]",
+                "[1,1,3,1,1]",
+                "[1,1,5,1,1]",
+                "[[1],[2,3,4]]",
+                "[[1],4]"
+            ]
+        );
+        assert_eq!(sorted.binary_search(&node("[1,1,5,1,1]")), Ok(3));
+
+        // Equal by the puzzle rules, but still distinct values
+        assert_eq!(compare_nodes(&node("[1]"), &node("[[1]]")), Ordering::Equal);
+        assert!(node("[1]") < node("[[1]]"));
+        assert_eq!(
+            [node("[[1]]"), node("[1]"), node("[1]")]
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        for line in ["[]", "[[[]]]", "[1,[2,[3,[4,[5,6,7]]]],8,9]", "[-1,[0]]"] {
+            assert_eq!(
+                parse_node(line).map(|it| it.to_string()).as_deref(),
+                Ok(line)
+            );
+        }
+    }
+
+    fn node_strategy() -> impl Strategy<Value = Node> {
+        // Few distinct integers so that ties and promotions actually come up
+        let leaf = (-2..3i64).prop_map(Node::Integer);
+        leaf.prop_recursive(4, 32, 4, |inner| {
+            prop::collection::vec(inner, 0..4).prop_map(Node::List)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_order_is_total(a in node_strategy(), b in node_strategy(), c in node_strategy()) {
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            prop_assert_eq!(a.cmp(&b) == Ordering::Equal, a == b);
+
+            if a <= b && b <= c {
+                prop_assert!(a <= c);
+            }
+        }
+
+        #[test]
+        fn prop_order_follows_puzzle(a in node_strategy(), b in node_strategy()) {
+            let puzzle = compare_nodes(&a, &b);
+            if puzzle.is_ne() {
+                prop_assert_eq!(a.cmp(&b), puzzle);
+            }
+        }
+
+        #[test]
+        fn prop_display_round_trips(a in node_strategy()) {
+            // Only lists are packets on their own
+            let packet = Node::List(vec![a]);
+            prop_assert_eq!(parse_node(&packet.to_string()), Ok(packet));
+        }
+    }
+
+    #[test]
+    fn test_json_conversion() {
+        let node = parse_node("[[1],[2,-3],[]]").expect("Valid packet");
+        let json = Value::from(&node);
+        assert_eq!(json, serde_json::json!([[1], [2, -3], []]));
+        assert_eq!(Node::try_from(&json).ok(), Some(node));
+
+        let err = |json: Value| Node::try_from(&json).map_err(|it| it.to_string());
+        assert_eq!(
+            err(serde_json::json!([1, [2, 2.5]])),
+            Err("$[1][1]: packets only hold integers and lists, found float 2.5".to_string())
+        );
+        assert_eq!(
+            err(serde_json::json!([["a"]])),
+            Err("$[0][0]: packets only hold integers and lists, found string \"a\"".to_string())
+        );
+        assert_eq!(
+            err(serde_json::json!({"a": 1})),
+            Err("$: packets only hold integers and lists, found object".to_string())
+        );
+        assert!(err(serde_json::json!([u64::MAX])).is_err());
+    }
+
+    #[test]
+    fn test_read_json_pairs() {
+        let inp = include_str!("../input/2022/day13_test.txt");
+        let pairs = read_json_pairs(inp.as_bytes()).expect("Test input is valid JSON");
+        let packets = generate(inp).expect("Failed parsing test input");
+        assert_eq!(pairs.len(), 8);
+        assert_eq!(
+            pairs.into_iter().flat_map(<[Node; 2]>::from).collect_vec(),
+            packets
+        );
+
+        let pretty = "[\n  1,\n  [2]\n]\n[[1, 2]]";
+        let pairs = read_json_pairs(pretty.as_bytes()).expect("Valid JSON");
+        assert_eq!(pairs[0].0.to_string(), "[1,[2]]");
+        assert_eq!(pairs[0].1.to_string(), "[[1,2]]");
+
+        let err = read_json_pairs(&b"[1] [2] [null]"[..]).expect_err("Null is not a packet");
+        assert_eq!(
+            err.to_string(),
+            "packet 3: $[0]: packets only hold integers and lists, found null"
+        );
+        assert!(matches!(
+            read_json_pairs(&b"[1] [2] [3]"[..]),
+            Err(JsonError::UnpairedPacket { packet: 3 })
+        ));
+        assert!(matches!(
+            read_json_pairs(&b"[1] [2"[..]),
+            Err(JsonError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn test_explain() {
+        let node = |line: &str| parse_node(line).expect("Valid packet");
+
+        let explanation = explain(&node("[[1],[2,3,4]]"), &node("[[1],4]"));
+        assert_eq!(explanation.order, Ordering::Less);
+        assert_eq!(explanation.decision, Some(Decision::Integers(2, 4)));
+        assert_eq!(explanation.left_path, vec![1, 0]);
+        assert_eq!(explanation.right_path, vec![1]);
+        assert_eq!(
+            explanation.to_string(),
+            "- Compare [[1],[2,3,4]] vs [[1],4]\n\
+             \x20 - Compare [1] vs [1]\n\
+             \x20   - Compare 1 vs 1\n\
+             \x20 - Compare [2,3,4] vs 4\n\
+             \x20   - Mixed types; convert right to [4] and retry comparison\n\
+             \x20   - Compare [2,3,4] vs [4]\n\
+             \x20     - Compare 2 vs 4\n\
+             \x20       - Left side is smaller, so inputs are in the right order"
+        );
+
+        let explanation = explain(&node("[9]"), &node("[[8,7,6]]"));
+        assert_eq!(explanation.decision, Some(Decision::Integers(9, 8)));
+        assert_eq!(explanation.left_path, vec![0]);
+        assert_eq!(explanation.right_path, vec![0, 0]);
+        assert_eq!(
+            explanation.trace[1..],
+            [
+                "  - Compare 9 vs [8,7,6]",
+                "    - Mixed types; convert left to [9] and retry comparison",
+                "    - Compare [9] vs [8,7,6]",
+                "      - Compare 9 vs 8",
+                "        - Right side is smaller, so inputs are not in the right order"
+            ]
+        );
+
+        let explanation = explain(&node("[[[]]]"), &node("[[]]"));
+        assert_eq!(explanation.order, Ordering::Greater);
+        assert_eq!(explanation.decision, Some(Decision::RightRanOut));
+        assert_eq!(explanation.left_path, vec![0]);
+        assert_eq!(explanation.right_path, vec![0]);
+
+        let explanation = explain(&node("[1,[2]]"), &node("[1,2]"));
+        assert_eq!(explanation.order, Ordering::Equal);
+        assert_eq!(explanation.decision, None);
+        assert!(explanation.left_path.is_empty() && explanation.right_path.is_empty());
+    }
+
+    #[test]
+    fn test_explain_pairs() {
+        let inp = include_str!("../input/2022/day13_test.txt");
+        let data = generate(inp).expect("Failed parsing test input");
+        let text = explain_pairs(&data);
+
+        assert!(text.starts_with(
+            "== Pair 1 ==\n\
+             - Compare [1,1,3,1,1] vs [1,1,5,1,1]\n\
+             \x20 - Compare 1 vs 1\n\
+             \x20 - Compare 1 vs 1\n\
+             \x20 - Compare 3 vs 5\n\
+             \x20   - Left side is smaller, so inputs are in the right order\n\
+             \n\
+             == Pair 2 =="
+        ));
+        assert!(text.contains(
+            "== Pair 4 ==\n\
+             - Compare [[4,4],4,4] vs [[4,4],4,4,4]\n\
+             \x20 - Compare [4,4] vs [4,4]\n\
+             \x20   - Compare 4 vs 4\n\
+             \x20   - Compare 4 vs 4\n\
+             \x20 - Compare 4 vs 4\n\
+             \x20 - Compare 4 vs 4\n\
+             \x20 - Left side ran out of items, so inputs are in the right order"
+        ));
+
+        let right_order = data
+            .iter()
+            .tuples()
+            .positions(|(l, r)| explain(l, r).order.is_lt())
+            .map(|idx| idx + 1)
+            .sum::<usize>();
+        assert_eq!(right_order, part1(&data));
+    }
+
+    #[test]
+    fn test_divider_positions() {
+        let inp = include_str!("../input/2022/day13_test.txt");
+        let data = generate(inp).expect("Failed parsing test input");
+        let dividers = decoder_dividers();
+
+        assert_eq!(divider_positions(&data, &dividers), vec![10, 14]);
+
+        let sorted = sorted_with_dividers(&data, &dividers);
+        assert_eq!(sorted.len(), 18);
+        assert_eq!(sorted[9], dividers[0]);
+        assert_eq!(sorted[13], dividers[1]);
+        assert!(sorted.iter().tuple_windows().all(|(a, b)| a <= b));
+
+        // Other dividers, given in any order
+        let dividers = ["[[10]]", "[]", "[1,1]"].map(|it| parse_node(it).expect("Valid packet"));
+        let positions = divider_positions(&data, &dividers);
+        let sorted = sorted_with_dividers(&data, &dividers);
+        for (divider, position) in dividers.iter().zip(&positions) {
+            assert_eq!(&sorted[position - 1], divider);
+        }
+        assert_eq!(positions, vec![19, 1, 5]);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::day04::{IntervalSet, Range};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use parse_display_derive::{Display, FromStr};
@@ -61,19 +62,18 @@ fn get_taken_coords(sensors: &[Sensor]) -> HashSet<(isize, isize)> {
 
 #[allow(clippy::cast_possible_wrap)]
 fn count_in_row(target_row: isize, sensors: &[Sensor]) -> Option<usize> {
-    let min_x = sensors.iter().map(|it| it.x - it.distance).min()?;
-    let max_x = sensors.iter().map(|it| it.x + it.distance).max()?;
-
-    let range = (min_x..=max_x).collect_vec();
-
-    let taken_spots = get_taken_coords(sensors);
-
-    let result = range
-        .iter()
-        .filter(|it| can_be_beacon((**it, target_row), sensors, &taken_spots) == Some(false))
+    let covered = IntervalSet::from_ranges(sensors.iter().filter_map(|it| {
+        let reach = it.distance - it.y.abs_diff(target_row) as isize;
+        (reach >= 0).then(|| Range::new((it.x - reach) as i64, (it.x + reach) as i64))
+    }));
+
+    // Sensors and beacons in the row are known, so they don't count
+    let taken_in_row = get_taken_coords(sensors)
+        .into_iter()
+        .filter(|&(x, y)| y == target_row && covered.contains(x as i64))
         .count();
 
-    Some(result)
+    (covered.len() as usize).checked_sub(taken_in_row)
 }
 
 #[aoc(day15, part1)]
@@ -112,11 +112,11 @@ fn print_map(tiles: &[Vec<Tile>]) {
 
 fn grow_map(tiles: &mut Vec<Vec<Tile>>) {
     // extend map
-    if tiles[0].iter().any(|it| *it == Tile::Elf) {
+    if tiles[0].contains(&Tile::Elf) {
         tiles.insert(0, vec![Tile::Empty; tiles[0].len()]);
     }
 
-    if tiles[tiles.len() - 1].iter().any(|it| *it == Tile::Elf) {
+    if tiles[tiles.len() - 1].contains(&Tile::Elf) {
         tiles.push(vec![Tile::Empty; tiles[0].len()]);
     }
 